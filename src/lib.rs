@@ -1,256 +1,5934 @@
-#![allow(mixed_script_confusables)]
+#![allow(confusable_idents, mixed_script_confusables)]
 //! Only the binary may be stable, the library cannot!
-use std::{collections::HashMap, error::Error, fs, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    env,
+    error::Error,
+    fmt, fs,
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 pub fn read_data(path: &Path) -> Result<Data, Box<dyn Error>> {
-    let data = fs::read_to_string(path)?;
+    read_data_as(path, Format::of(path))
+}
 
-    Ok(toml::from_str(&data)?)
+pub fn read_data_as(path: &Path, format: Format) -> Result<Data, Box<dyn Error>> {
+    parse_data(&fs::read_to_string(path)?, format)
 }
 
-pub fn write_data(path: &Path, data: &Data) -> Result<(), Box<dyn Error>> {
-    let str = toml::to_string(data)?;
+/// A leading UTF-8 byte order mark, as written by some Windows editors, is ignored
+///
+/// Files of a newer [`DATA_VERSION`] are rejected.
+pub fn parse_data(str: &str, format: Format) -> Result<Data, Box<dyn Error>> {
+    let str = str.strip_prefix('\u{feff}').unwrap_or(str);
+
+    // Checked first, newer files may not parse at all
+    #[derive(Deserialize)]
+    struct Version {
+        #[serde(default)]
+        version: u32,
+    }
+
+    let version = match format {
+        Format::Toml => toml::from_str::<Version>(str)?.version,
+        Format::Json => from_json::<Version>(str)?.version,
+    };
+    if version > DATA_VERSION {
+        return Err(format!(
+            "the file was written by a newer ultira (data version {version}, this one supports up to {DATA_VERSION}), upgrade ultira to read it"
+        )
+        .into());
+    }
+
+    let mut data: Data = match format {
+        Format::Toml => toml::from_str(str)?,
+        Format::Json => from_json(str)?,
+    };
+    // Older versions are still valid, and are written as the current one
+    data.version = DATA_VERSION;
 
-    Ok(fs::write(path, str)?)
+    data.check_finite()?;
+    if let Some(max) = data.config.max_rating_change_per_play {
+        Config::check_max_rating_change(max)?;
+    }
+    Config::check_date_format(&data.config.date_format)?;
+    data.config.check_display_range()?;
+
+    Ok(data)
 }
 
-/// Returns the final ratings
-///
-/// ```
-/// use ultira::rating_change;
-///
-/// let α = 0.1;
-/// let game_count = 1;
-/// let ratings = [0.0, 0.0, 0.0];
-/// let scores = [4, -2, -2];
+/// Calls `on_change` with the data read again whenever the file's modification time or size changes, checking every `interval`
 ///
-/// let new_ratings = rating_change(α, game_count, ratings, scores);
-/// let expected_ratings = [0.4, -0.2, -0.2];
+/// It's also called once at the start, and with the error if the file can't be read. Returns once `on_change` returns false.
+pub fn watch(
+    path: &Path,
+    interval: Duration,
+    mut on_change: impl FnMut(Result<Data, Box<dyn Error>>) -> bool,
+) {
+    let mut last = None;
+
+    loop {
+        let stamp = fs::metadata(path)
+            .ok()
+            .map(|metadata| (metadata.modified().ok(), metadata.len()));
+
+        if last != Some(stamp) {
+            last = Some(stamp);
+            if !on_change(read_data(path)) {
+                return;
+            }
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+/// Reads a line with a 1-based index into `options`, `None` if it isn't a valid index or nothing could be read
+pub fn pick<'o>(options: &[&'o str], input: &mut impl io::BufRead) -> Option<&'o str> {
+    let mut line = String::new();
+    input.read_line(&mut line).ok()?;
+
+    let index: usize = line.trim().parse().ok()?;
+    options.get(index.checked_sub(1)?).copied()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// The file was readable, nothing was done
+    Intact,
+    /// The file was restored from this backup
+    FromBackup(PathBuf),
+}
+
+/// Restores the file from the most recent readable backup if it can't be read
 ///
-/// new_ratings
-///     .iter()
-///     .zip(expected_ratings.iter())
-///     .for_each(|(a, b)| assert!((a - b).abs() < 0.0001));
-/// ```
-pub fn rating_change(α: f64, games: usize, ratings: [f64; 3], scores: [i64; 3]) -> [f64; 3] {
-    let average_rating = ratings.iter().sum::<f64>() / 3.0;
+/// Backups are files next to it named `<file name>*.bak`, e.g. `ultira.toml.bak`. The unreadable file is kept as `<file name>.corrupt`.
+pub fn repair(path: &Path) -> Result<Repair, Box<dyn Error>> {
+    if read_data(path).is_ok() {
+        return Ok(Repair::Intact);
+    }
 
-    let mut new_ratings = [0.0; 3];
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("invalid file name")?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
 
-    for i in 0..3 {
-        let r_i = ratings[i];
-        let r_avg = average_rating;
-        let g = games as i32;
-        let s_i_avg = scores[i] as f64 / games as f64;
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+
+        if name.starts_with(file_name) && name.ends_with(".bak") {
+            backups.push((entry.metadata()?.modified()?, entry.path()));
+        }
+    }
+    backups.sort_unstable_by(|(modified_a, _), (modified_b, _)| modified_b.cmp(modified_a));
 
-        new_ratings[i] = (1.0 - α).powi(g) * r_i + (r_avg + s_i_avg) * (1.0 - (1.0 - α).powi(g));
+    for (_, backup) in backups {
+        let Ok(data) = read_data_as(&backup, Format::of(path)) else {
+            continue;
+        };
 
-        assert!(new_ratings[i].is_finite());
+        if path.exists() {
+            fs::rename(path, dir.join(format!("{file_name}.corrupt")))?;
+        }
+        write_data(path, &data)?;
+
+        return Ok(Repair::FromBackup(backup));
     }
 
-    new_ratings
+    Err(format!(
+        "{} is unreadable and no readable backup was found",
+        path.to_string_lossy()
+    )
+    .into())
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
-pub struct Data {
-    pub config: Config,
-    pub history: Vec<Change>,
-}
+/// Comments and formatting of an existing TOML file are kept where the data they belong to still exists
+///
+/// Files without comments are written as new, as merging is several times slower than writing.
+///
+/// Stored ratings are rounded to the configured `store_precision`.
+pub fn write_data(path: &Path, data: &Data) -> Result<(), Box<dyn Error>> {
+    // Otherwise the written file couldn't be read back
+    data.check_finite()?;
 
-impl Data {
-    pub fn evaluate(&self) -> Evaluation {
-        let mut α = self.config.starting_alpha;
-        let mut ratings: HashMap<String, f64> = HashMap::new();
+    let rounded;
+    let data = match data.config.store_precision {
+        Some(precision) => {
+            rounded = data.with_store_precision(precision);
+            &rounded
+        }
+        None => data,
+    };
 
-        for change in &self.history {
-            match change {
-                Change::AddPlayer(addition) => {
-                    ratings.insert(addition.name.clone(), addition.rating);
-                }
-                Change::Play(play) => {
-                    let selected_ratings: [f64; 3] = play
-                        .outcomes
-                        .clone()
-                        .map(|outcome| ratings[&outcome.player]);
-                    let scores = play.outcomes.clone().map(|outcome| outcome.score);
-                    let new_ratings = rating_change(α, play.game_count, selected_ratings, scores);
-
-                    for i in 0..3 {
-                        *ratings.get_mut(&play.outcomes[i].player).unwrap() = new_ratings[i];
-                    }
+    let str = match Format::of(path) {
+        Format::Toml => {
+            let new = toml::to_string(data)?;
+
+            match fs::read_to_string(path)
+                .ok()
+                // A `#` may also be part of a string, then the merge is just unnecessary
+                .filter(|old| old.contains('#'))
+                .and_then(|old| old.parse::<toml_edit::Document>().ok())
+            {
+                Some(mut old) => {
+                    let mut new = new.parse::<toml_edit::Document>()?;
+                    merge_toml_table(old.as_table_mut(), mem::take(new.as_table_mut()));
+                    renumber_toml_tables(old.as_table_mut(), &mut 0);
+                    old.to_string()
                 }
-                Change::AdjustAlpha(new) => α = *new,
+                None => new,
             }
         }
+        Format::Json => to_json(data)?,
+    };
 
-        Evaluation { α, ratings }
-    }
+    write_atomically(path, str.as_bytes())
+}
 
-    pub fn add_player(&mut self, name: String, rating: f64) {
-        self.history
-            .push(Change::AddPlayer(AddPlayer { name, rating }));
+/// Writes to `<file name>.tmp` next to `path` first, then renames it over `path`, so readers never see a half-written file
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    fs::write(&tmp_path, contents)?;
+    if let Err(err) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err.into());
     }
 
-    pub fn add_player_display(&mut self, name: String, display: f64) {
-        self.add_player(name, self.config.rating_from_display(display));
+    Ok(())
+}
+
+/// Checks that the file at `path` evaluates to the same ratings and α as `data`, e.g. right after writing it
+///
+/// Catches data lost by serialization, such as float formatting. Ratings rounded by `store_precision` are expected.
+pub fn verify_write(path: &Path, data: &Data) -> Result<(), Box<dyn Error>> {
+    let expected = match data.config.store_precision {
+        Some(precision) => data.with_store_precision(precision).evaluate(),
+        None => data.evaluate(),
+    };
+    let written = read_data(path)?.evaluate();
+
+    if written.α != expected.α {
+        return Err(format!("α reads back as {} instead of {}", written.α, expected.α).into());
     }
 
-    pub fn play(&mut self, play: Play) {
-        self.history.push(Change::Play(play));
+    let mut players: Vec<&String> = expected
+        .ratings
+        .keys()
+        .chain(written.ratings.keys())
+        .collect();
+    players.sort_unstable();
+    players.dedup();
+
+    for player in players {
+        let (expected, written) = (expected.ratings.get(player), written.ratings.get(player));
+        if expected != written {
+            return Err(format!(
+                "rating of '{player}' reads back as {written:?} instead of {expected:?}"
+            )
+            .into());
+        }
     }
 
-    pub fn adjust_α(&mut self, new: f64) {
-        self.history.push(Change::AdjustAlpha(new));
+    Ok(())
+}
+
+/// Makes `old` equal to `new`, keeping the decoration (e.g. comments) of items existing in both
+///
+/// Tables of arrays of tables, such as the history, are matched by content in order, so comments stay with their entry when others are removed or inserted. Changed entries lose their decoration.
+fn merge_toml(old: &mut toml_edit::Item, new: toml_edit::Item) {
+    use toml_edit::Item;
+
+    match (old, new) {
+        (Item::Table(old), Item::Table(new)) => merge_toml_table(old, new),
+        (Item::ArrayOfTables(old), Item::ArrayOfTables(new)) => {
+            let mut unmatched: HashMap<String, VecDeque<usize>> = HashMap::new();
+            for (i, table) in old.iter().enumerate() {
+                unmatched
+                    .entry(toml_table_content(table))
+                    .or_default()
+                    .push_back(i);
+            }
+            let mut old_tables: Vec<Option<toml_edit::Table>> =
+                mem::take(old).into_iter().map(Some).collect();
+
+            // Index of the first old table which can still be matched, matches keep their order
+            let mut next = 0;
+            let mut merged = toml_edit::ArrayOfTables::new();
+            for new in new {
+                let matched = unmatched
+                    .get_mut(&toml_table_content(&new))
+                    .and_then(|indices| {
+                        while indices.front().is_some_and(|&i| i < next) {
+                            indices.pop_front();
+                        }
+                        indices.pop_front()
+                    });
+
+                match matched.and_then(|i| {
+                    next = i + 1;
+                    old_tables[i].take()
+                }) {
+                    Some(old) => merged.push(old),
+                    None => merged.push(new),
+                }
+            }
+
+            *old = merged;
+        }
+        (Item::Value(old), Item::Value(new)) => {
+            let decor = old.decor().clone();
+            *old = new;
+            *old.decor_mut() = decor;
+        }
+        (old, new) => *old = new,
     }
+}
 
-    pub fn adjust_score_multiplier(&mut self, new: f64) {
-        self.adjust_α(self.config.α_from_display(new));
+fn merge_toml_table(old: &mut toml_edit::Table, new: toml_edit::Table) {
+    old.retain(|key, _| new.contains_key(key));
+
+    for (key, new) in new {
+        match old.get_mut(&key) {
+            Some(item) if mem::discriminant(item) == mem::discriminant(&new) => {
+                merge_toml(item, new)
+            }
+            // The decoration of the key doesn't fit a different kind of item, e.g. `history = []` becoming `[[history]]`
+            _ => {
+                old.remove(&key);
+                old.insert(&key, new);
+            }
+        }
     }
+}
 
-    pub fn rename(&mut self, old_name: &str, new_name: &str) {
-        for elem in &mut self.history {
-            match elem {
-                Change::AddPlayer(p) => {
-                    if p.name == old_name {
-                        p.name = new_name.to_owned();
-                    }
+/// The content of `table` without decoration, equal for equal tables of different documents
+///
+/// Tables and inline tables with the same entries have the same content.
+fn toml_table_content(table: &toml_edit::Table) -> String {
+    let mut content = String::new();
+    push_toml_table_content(table, &mut content);
+    content
+}
+
+fn push_toml_table_content(table: &toml_edit::Table, content: &mut String) {
+    let mut entries: Vec<_> = table.iter().collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+
+    content.push('{');
+    for (key, item) in entries {
+        content.push_str(&format!("{key:?}="));
+        match item {
+            toml_edit::Item::None => {}
+            toml_edit::Item::Value(value) => push_toml_value_content(value, content),
+            toml_edit::Item::Table(table) => push_toml_table_content(table, content),
+            toml_edit::Item::ArrayOfTables(tables) => {
+                content.push('[');
+                for table in tables {
+                    push_toml_table_content(table, content);
+                    content.push(',');
                 }
-                Change::Play(p) => {
-                    for outcome in &mut p.outcomes {
-                        if outcome.player == old_name {
-                            outcome.player = new_name.to_owned();
-                        }
-                    }
+                content.push(']');
+            }
+        }
+        content.push(',');
+    }
+    content.push('}');
+}
+
+fn push_toml_value_content(value: &toml_edit::Value, content: &mut String) {
+    use toml_edit::Value;
+
+    match value {
+        Value::String(string) => content.push_str(&format!("{:?}", string.value())),
+        Value::Integer(integer) => content.push_str(&format!("{}", integer.value())),
+        Value::Float(float) => content.push_str(&format!("{:?}", float.value())),
+        Value::Boolean(boolean) => content.push_str(&format!("{}", boolean.value())),
+        Value::Datetime(datetime) => content.push_str(&format!("d{}", datetime.value())),
+        Value::Array(array) => {
+            content.push('[');
+            for value in array {
+                push_toml_value_content(value, content);
+                content.push(',');
+            }
+            content.push(']');
+        }
+        Value::InlineTable(table) => {
+            let mut entries: Vec<_> = table.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+
+            content.push('{');
+            for (key, value) in entries {
+                content.push_str(&format!("{key:?}="));
+                push_toml_value_content(value, content);
+                content.push(',');
+            }
+            content.push('}');
+        }
+    }
+}
+
+/// Orders the tables as they are nested, tables from different documents may have conflicting positions
+fn renumber_toml_tables(table: &mut toml_edit::Table, position: &mut usize) {
+    table.set_position(*position);
+    *position += 1;
+
+    for (_, item) in table.iter_mut() {
+        match item {
+            toml_edit::Item::Table(table) => renumber_toml_tables(table, position),
+            toml_edit::Item::ArrayOfTables(tables) => {
+                for table in tables.iter_mut() {
+                    renumber_toml_tables(table, position);
                 }
-                Change::AdjustAlpha(_) => {}
             }
+            _ => {}
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Config {
-    pub spread: f64,
-    pub base_rating: f64,
-    pub starting_alpha: f64,
+/// An advisory lock on a data file, released when dropped
+#[derive(Debug)]
+pub struct FileLock {
+    _file: fs::File,
 }
 
-impl Default for Config {
+/// Locks `path` against other ultira processes, e.g. around a read-modify-write of a shared file
+///
+/// The lock is taken on `<file name>.lock` next to it, as the file itself may be replaced. If `wait` is false and another process holds the lock, this fails immediately instead of waiting.
+///
+/// The lock file is left in place when the lock is released. Removing it would let a process still waiting on the removed file and one creating a new lock file hold the lock at the same time.
+pub fn lock(path: &Path, wait: bool) -> Result<FileLock, Box<dyn Error>> {
+    use fs2::FileExt;
+
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock_error = |err: io::Error| {
+        format!(
+            "can't lock {}: {err}",
+            Path::new(&lock_path).to_string_lossy()
+        )
+    };
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(lock_error)?;
+
+    if wait {
+        file.lock_exclusive().map_err(lock_error)?;
+    } else if let Err(err) = file.try_lock_exclusive() {
+        if err.kind() == fs2::lock_contended_error().kind() {
+            return Err(format!(
+                "{} is locked by another process, try again later or pass --wait-for-lock",
+                path.to_string_lossy()
+            )
+            .into());
+        }
+        return Err(lock_error(err).into());
+    }
+
+    Ok(FileLock { _file: file })
+}
+
+/// Appends a timestamped line with the current user to the audit log
+pub fn append_audit(audit_log: &Path, entry: &str) -> Result<(), Box<dyn Error>> {
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned());
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(audit_log)?;
+
+    writeln!(
+        file,
+        "{} {user}: {entry}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    )?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableOptions {
+    pub layout: Layout,
+    /// Only these players are included, in this order; all players by name if `None`
+    pub players: Option<Vec<String>>,
+    /// Separates the cells of a row, a tab by default
+    pub delimiter: char,
+    /// Use a comma as the decimal separator
+    pub decimal_comma: bool,
+}
+
+impl Default for TableOptions {
     fn default() -> Self {
         Self {
-            spread: 50.0,
-            base_rating: 100.0,
-            starting_alpha: 0.02,
+            layout: Layout::default(),
+            players: None,
+            delimiter: '\t',
+            decimal_comma: false,
         }
     }
 }
 
-impl Config {
-    pub fn rating_from_display(&self, display: f64) -> f64 {
-        (display - self.base_rating) / self.spread
+impl TableOptions {
+    /// Fails if cells couldn't be told apart from the decimals of the ratings
+    pub fn check(&self) -> Result<(), String> {
+        if self.delimiter == '\n' {
+            return Err("the delimiter can't be a newline".to_owned());
+        }
+
+        let decimal_separator = if self.decimal_comma { ',' } else { '.' };
+        if self.delimiter == decimal_separator || self.delimiter.is_ascii_digit() {
+            return Err(format!(
+                "the delimiter '{}' is ambiguous with the ratings' digits and decimal separator",
+                self.delimiter
+            ));
+        }
+
+        Ok(())
     }
+}
 
-    pub fn rating_to_display(&self, rating: f64) -> f64 {
-        rating * self.spread + self.base_rating
+/// Shape of exported tables
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// One row per time point, one column per player
+    #[default]
+    Wide,
+    /// One row per time point and player
+    Tidy,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wide" => Ok(Self::Wide),
+            "tidy" => Ok(Self::Tidy),
+            _ => Err(format!("expected 'wide' or 'tidy', found '{s}'")),
+        }
     }
+}
 
-    pub fn α_from_display(&self, display: f64) -> f64 {
-        display / self.spread
+/// File format of exported ratings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TableFormat {
+    /// Tab separated ratings over time, see [`Data::ratings_table`]
+    #[default]
+    Tsv,
+    /// Current standings as a GitHub-flavored Markdown table, see [`Data::standings_markdown`]
+    Markdown,
+}
+
+impl FromStr for TableFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tsv" => Ok(Self::Tsv),
+            "md" | "markdown" => Ok(Self::Markdown),
+            _ => Err(format!("expected 'tsv' or 'md', found '{s}'")),
+        }
     }
+}
 
-    pub fn α_to_display(&self, α: f64) -> f64 {
-        α * self.spread
+/// Output format of reports, such as the problems found by checks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// A JSON array, e.g. for scripts and git hooks
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("expected 'text' or 'json', found '{s}'")),
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize, Serialize)]
-#[non_exhaustive]
-#[serde(rename_all = "snake_case")]
-pub enum Change {
-    AddPlayer(AddPlayer),
-    Play(Play),
-    AdjustAlpha(f64),
+/// How head-to-head results are split up, see [`Data::head_to_head`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    #[default]
+    None,
+    /// By calendar year of the plays
+    Year,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
-pub struct AddPlayer {
-    pub name: String,
-    pub rating: f64,
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "year" => Ok(Self::Year),
+            _ => Err(format!("expected 'none' or 'year', found '{s}'")),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
-pub struct Play {
-    pub game_count: usize,
-    #[serde(with = "toml_datetime_compat")]
-    pub date: chrono::NaiveDate,
-    pub outcomes: [Outcome; 3],
+/// Storage format of the data file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
 }
 
-impl Play {
-    pub fn now(game_count: usize, outcomes: [Outcome; 3]) -> Self {
-        Play {
-            game_count,
-            date: chrono::Local::now().date_naive(),
-            outcomes,
+impl Format {
+    /// Decided by the file extension, defaults to TOML
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Toml,
         }
     }
 }
 
-#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
-pub struct Outcome {
-    pub player: String,
-    pub score: i64,
+/// Serializes to pretty-printed JSON, with dates written as plain `YYYY-MM-DD` strings
+pub fn to_json<T: Serialize>(value: &T) -> Result<String, Box<dyn Error>> {
+    let mut value = serde_json::to_value(value)?;
+    flatten_toml_dates(&mut value);
+
+    Ok(serde_json::to_string_pretty(&value)?)
 }
 
-#[derive(Debug, Clone, Default, PartialEq)]
-pub struct Evaluation {
-    pub α: f64,
-    pub ratings: HashMap<String, f64>,
+pub fn from_json<T: DeserializeOwned>(str: &str) -> Result<T, Box<dyn Error>> {
+    Ok(serde_json::from_str(str)?)
 }
 
-impl Evaluation {
-    pub fn matching_names<'s>(&'s self, pattern: &'s str) -> Vec<&'s str> {
-        if self.ratings.keys().any(|name| name == pattern) {
-            return vec![pattern];
-        }
+/// TOML dates serialize as a single-keyed private table, which is meaningless outside of TOML
+fn flatten_toml_dates(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if map.len() == 1 {
+                if let Some(serde_json::Value::String(date)) = map.get(TOML_DATETIME_FIELD) {
+                    *value = serde_json::Value::String(date.clone());
+                    return;
+                }
+            }
 
-        self.ratings
-            .keys()
-            .filter(|name| match_names(name, pattern))
-            .map(|name| name.as_str())
-            .collect()
+            map.values_mut().for_each(flatten_toml_dates);
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(flatten_toml_dates),
+        _ => {}
     }
 }
 
-fn match_names(matched: &str, pattern: &str) -> bool {
-    let mut split_name = matched.split(' ').filter(|x| !x.is_empty());
-    let split_pattern = pattern.split(' ').filter(|x| !x.is_empty());
+const TOML_DATETIME_FIELD: &str = "$__toml_private_datetime";
 
-    for pattern_word in split_pattern {
-        loop {
-            match split_name.next() {
-                Some(word) if word.starts_with(pattern_word) => break,
-                Some(_word) => {}
-                None => return false,
-            }
+/// Dates are stored as native TOML dates, but plain strings are also accepted (e.g. from JSON)
+mod date {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        date: &chrono::NaiveDate,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        toml_datetime_compat::serialize(date, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<chrono::NaiveDate, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Toml(#[serde(with = "toml_datetime_compat")] chrono::NaiveDate),
+            Plain(chrono::NaiveDate),
         }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Toml(date) | Repr::Plain(date) => date,
+        })
     }
 
-    true
+    pub mod optional {
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            date: &Option<chrono::NaiveDate>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match date {
+                Some(date) => super::serialize(date, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<chrono::NaiveDate>, D::Error> {
+            #[derive(Deserialize)]
+            struct Wrapper(#[serde(with = "super")] chrono::NaiveDate);
+
+            Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(date)| date))
+        }
+    }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// One block character per value, scaled between the lowest and the highest value
+///
+/// If all values are equal, e.g. there is only one, every block is of medium height.
+pub fn sparkline(values: &[f64]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    values
+        .iter()
+        .map(|value| {
+            if max - min <= 0.0 {
+                BLOCKS[BLOCKS.len() / 2 - 1]
+            } else {
+                let level = (value - min) / (max - min) * (BLOCKS.len() - 1) as f64;
+                BLOCKS[level.round() as usize]
+            }
+        })
+        .collect()
+}
+
+/// Score of the third player of a zero-sum play, `None` if it doesn't fit in an `i64`
+pub fn inferred_score(scores: [i64; 2]) -> Option<i64> {
+    scores[0].checked_add(scores[1])?.checked_neg()
+}
+
+/// Parses plays of a score log with `<date> <game count> <name> <score> <name> <score> <name> <score>` lines
+///
+/// Names may contain spaces, but not consist of a number. Empty lines and lines starting with `#` are skipped. Scores must sum to 0.
+/// Returns the 1-based line number with each play, names are returned as written.
+pub fn parse_play_log(log: &str) -> Result<Vec<(usize, Play)>, Box<dyn Error>> {
+    let mut plays = Vec::new();
+
+    for (i, line) in log.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = |message: &str| format!("line {line_number}: {message}");
+        let expected =
+            "expected '<date> <game count> <name> <score> <name> <score> <name> <score>'";
+
+        let mut words = line.split_whitespace();
+        let date: chrono::NaiveDate = words
+            .next()
+            .and_then(|date| date.parse().ok())
+            .ok_or_else(|| error("expected a date as YYYY-MM-DD"))?;
+        let game_count: usize = words
+            .next()
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(|| error("expected a game count"))?;
+
+        let mut outcomes = Vec::new();
+        let mut name: Vec<&str> = Vec::new();
+        for word in words {
+            match word.parse::<i64>() {
+                Ok(score) if !name.is_empty() => {
+                    outcomes.push(Outcome {
+                        player: name.join(" "),
+                        score,
+                    });
+                    name.clear();
+                }
+                Ok(_) => return Err(error(expected).into()),
+                Err(_) => name.push(word),
+            }
+        }
+
+        let Ok(outcomes) = <[Outcome; 3]>::try_from(outcomes) else {
+            return Err(error(expected).into());
+        };
+        if !name.is_empty() {
+            return Err(error(expected).into());
+        }
+
+        let sum: i64 = outcomes.iter().map(|o| o.score).sum();
+        if sum != 0 {
+            return Err(error(&format!("scores sum to {sum} instead of 0")).into());
+        }
+
+        plays.push((
+            line_number,
+            Play {
+                game_count,
+                date,
+                outcomes,
+                note: None,
+                fractional_games: None,
+                session: None,
+            },
+        ));
+    }
+
+    Ok(plays)
+}
+
+/// Every name mentioned by `changes`
+fn player_names<'c>(changes: impl IntoIterator<Item = &'c Change>) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+
+    for change in changes {
+        match change {
+            Change::AddPlayer(addition) => {
+                names.insert(addition.name.clone());
+            }
+            Change::Play(play) => {
+                names.extend(play.outcomes.iter().map(|outcome| outcome.player.clone()));
+            }
+            Change::SetRating(set) => {
+                names.insert(set.player.clone());
+            }
+            Change::AdjustAlpha(_) | Change::SeasonBoundary(_) => {}
+        }
+    }
+
+    names
+}
+
+/// Player names must contain something other than whitespace, as blank names can't be matched reliably
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        Err("player names can't be empty".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+/// A file name for `name` without path separators or other characters some file systems reject, e.g. "A/B" becomes "A_B"
+///
+/// Different names may give the same file name.
+pub fn file_name_for(name: &str) -> String {
+    let sanitized: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let sanitized = sanitized.trim_start_matches('.');
+
+    if sanitized.is_empty() {
+        "_".to_owned()
+    } else {
+        sanitized.to_owned()
+    }
+}
+
+/// Returns the final ratings
+///
+/// ```
+/// use ultira::rating_change;
+///
+/// let α = 0.1;
+/// let game_count = 1;
+/// let ratings = [0.0, 0.0, 0.0];
+/// let scores = [4, -2, -2];
+///
+/// let new_ratings = rating_change(α, game_count, ratings, scores);
+/// let expected_ratings = [0.4, -0.2, -0.2];
+///
+/// new_ratings
+///     .iter()
+///     .zip(expected_ratings.iter())
+///     .for_each(|(a, b)| assert!((a - b).abs() < 0.0001));
+/// ```
+pub fn rating_change(α: f64, games: usize, ratings: [f64; 3], scores: [i64; 3]) -> [f64; 3] {
+    rating_change_individual([α; 3], games, ratings, scores)
+}
+
+/// Same as [`rating_change`], but each player has their own α
+pub fn rating_change_individual(
+    alphas: [f64; 3],
+    games: usize,
+    ratings: [f64; 3],
+    scores: [i64; 3],
+) -> [f64; 3] {
+    rating_change_fractional(alphas, games as f64, ratings, scores)
+}
+
+/// Same as [`rating_change_individual`], but the number of games may be fractional, e.g. for interrupted sessions
+pub fn rating_change_fractional(
+    alphas: [f64; 3],
+    games: f64,
+    ratings: [f64; 3],
+    scores: [i64; 3],
+) -> [f64; 3] {
+    let average_rating = ratings.iter().sum::<f64>() / 3.0;
+
+    let mut new_ratings = [0.0; 3];
+
+    for i in 0..3 {
+        let α = alphas[i];
+        let r_i = ratings[i];
+        let r_avg = average_rating;
+        let s_i_avg = scores[i] as f64 / games;
+        // Whole game counts keep using powi, so that their results stay bit-identical
+        let decay = if games.fract() == 0.0 {
+            (1.0 - α).powi(games as i32)
+        } else {
+            (1.0 - α).powf(games)
+        };
+
+        new_ratings[i] = decay * r_i + (r_avg + s_i_avg) * (1.0 - decay);
+
+        assert!(new_ratings[i].is_finite());
+    }
+
+    new_ratings
+}
+
+/// Version of the data format this library reads and writes
+///
+/// Files without a version are from before versioning, and are read as version 0.
+/// Version 2 stores α adjustments as tables with an optional date and reason.
+/// Version 3 adds fractional game counts, which older versions would round up.
+/// Version 4 adds play sessions, which older versions would drop.
+/// Version 5 adds the games played before a compaction, which older versions would drop.
+pub const DATA_VERSION: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Data {
+    #[serde(default)]
+    pub version: u32,
+    pub config: Config,
+    pub history: Vec<Change>,
+}
+
+impl Default for Data {
+    fn default() -> Self {
+        Self {
+            version: DATA_VERSION,
+            config: Config::default(),
+            history: Vec::new(),
+        }
+    }
+}
+
+impl Data {
+    /// Random data with `players` players at the base rating and `plays` plays dated in `dates`, in date order
+    ///
+    /// Every game of a play has a single winner scoring 2 while the others score -1.
+    pub fn generate(
+        players: usize,
+        plays: usize,
+        dates: std::ops::RangeInclusive<chrono::NaiveDate>,
+        rng: &mut impl rand::Rng,
+    ) -> Result<Data, Box<dyn Error>> {
+        if players < 3 {
+            return Err("at least 3 players are needed".into());
+        }
+        if dates.is_empty() {
+            return Err("the date range is empty".into());
+        }
+
+        let mut data = Data::default();
+        let names: Vec<String> = (1..=players).map(|i| format!("Player {i}")).collect();
+        for name in &names {
+            data.add_player(name.clone(), 0.0);
+        }
+
+        let days = (*dates.end() - *dates.start()).num_days() as u64;
+        let mut offsets: Vec<u64> = (0..plays).map(|_| rng.gen_range(0..=days)).collect();
+        offsets.sort_unstable();
+
+        for offset in offsets {
+            let seats = rand::seq::index::sample(rng, players, 3);
+            let game_count = rng.gen_range(1..=10);
+            let mut scores = [0; 3];
+            for _ in 0..game_count {
+                let winner = rng.gen_range(0..3);
+                for (i, score) in scores.iter_mut().enumerate() {
+                    *score += if i == winner { 2 } else { -1 };
+                }
+            }
+
+            data.play(Play {
+                game_count,
+                date: *dates.start() + chrono::Days::new(offset),
+                outcomes: [0, 1, 2].map(|i| Outcome {
+                    player: names[seats.index(i)].clone(),
+                    score: scores[i],
+                }),
+                note: None,
+                fractional_games: None,
+                session: None,
+            });
+        }
+
+        Ok(data)
+    }
+
+    pub fn evaluate(&self) -> Evaluation {
+        self.evaluate_with_progress(|_| {})
+    }
+
+    /// Same as [`Data::evaluate`], but `progress` is called with the number of applied changes after each change
+    pub fn evaluate_with_progress(&self, mut progress: impl FnMut(usize)) -> Evaluation {
+        let mut eval = Evaluation::new(&self.config);
+
+        for (i, change) in self.history.iter().enumerate() {
+            eval.change(change, &self.config);
+            progress(i + 1);
+        }
+
+        if self.config.auto_center {
+            eval.center();
+        }
+
+        eval
+    }
+
+    /// Same as [`Data::evaluate`], but consecutive plays of disjoint players are applied concurrently
+    ///
+    /// Other changes and plays sharing a player with an earlier play of the batch end the batch, so the results are identical. Only worth it for very large histories.
+    #[cfg(feature = "parallel")]
+    pub fn evaluate_parallel(&self) -> Evaluation {
+        let mut eval = Evaluation::new(&self.config);
+        let mut batch: Vec<&Play> = Vec::new();
+        let mut batch_players: HashSet<&str> = HashSet::new();
+
+        for change in &self.history {
+            if let Change::Play(play) = change {
+                let players = play.outcomes.iter().map(|o| o.player.as_str());
+                if players
+                    .clone()
+                    .all(|player| !batch_players.contains(player))
+                {
+                    batch_players.extend(players);
+                    batch.push(play);
+                    continue;
+                }
+            }
+
+            eval.apply_disjoint_plays(&batch, &self.config);
+            batch.clear();
+            batch_players.clear();
+
+            match change {
+                Change::Play(play) => {
+                    batch_players.extend(play.outcomes.iter().map(|o| o.player.as_str()));
+                    batch.push(play);
+                }
+                _ => eval.change(change, &self.config),
+            }
+        }
+        eval.apply_disjoint_plays(&batch, &self.config);
+
+        if self.config.auto_center {
+            eval.center();
+        }
+
+        eval
+    }
+
+    /// The α in force just before the change at `index` is applied, as [`Evaluation`] would hold it
+    ///
+    /// Indices past the end give the current α.
+    pub fn α_at(&self, index: usize) -> f64 {
+        self.history
+            .iter()
+            .take(index)
+            .rev()
+            .find_map(|change| match change {
+                Change::AdjustAlpha(adjust) => Some(adjust.value),
+                _ => None,
+            })
+            .unwrap_or(self.config.starting_alpha)
+    }
+
+    /// A copy with the ratings of added players and set ratings rounded to `precision` decimals
+    pub fn with_store_precision(&self, precision: u32) -> Data {
+        let scale = 10f64.powi(precision as i32);
+        let round = |rating: &mut f64| *rating = (*rating * scale).round() / scale;
+
+        let mut rounded = self.clone();
+        for change in &mut rounded.history {
+            match change {
+                Change::AddPlayer(addition) => round(&mut addition.rating),
+                Change::SetRating(set) => round(&mut set.rating),
+                Change::Play(_) | Change::AdjustAlpha(_) | Change::SeasonBoundary(_) => {}
+            }
+        }
+
+        rounded
+    }
+
+    /// A copy in which α is `α` throughout the whole history, to experiment with it without changing the data
+    ///
+    /// Not only the starting α is replaced, but every adjustment of it too, so that a single decay rate can be judged. Indices of the history are kept.
+    pub fn with_fixed_α(&self, α: f64) -> Data {
+        let mut data = self.clone();
+        data.config.starting_alpha = α;
+
+        for change in &mut data.history {
+            if let Change::AdjustAlpha(adjusted) = change {
+                adjusted.value = α;
+            }
+        }
+
+        data
+    }
+
+    /// Locations and values of the NaN and infinite numbers, which would spread through every evaluation
+    pub fn non_finite_numbers(&self) -> Vec<(String, f64)> {
+        let mut numbers = vec![
+            ("config.spread".to_owned(), self.config.spread),
+            ("config.base_rating".to_owned(), self.config.base_rating),
+            (
+                "config.starting_alpha".to_owned(),
+                self.config.starting_alpha,
+            ),
+        ];
+        if let Some(max) = self.config.max_rating_change_per_play {
+            numbers.push(("config.max_rating_change_per_play".to_owned(), max));
+        }
+        if let Some(floor) = self.config.rating_floor {
+            numbers.push(("config.rating_floor".to_owned(), floor));
+        }
+        if let Some(ceiling) = self.config.rating_ceiling {
+            numbers.push(("config.rating_ceiling".to_owned(), ceiling));
+        }
+        for (player, multiplier) in &self.config.player_alpha {
+            numbers.push((format!("config.player_alpha.{player}"), *multiplier));
+        }
+
+        for (i, change) in self.history.iter().enumerate() {
+            match change {
+                Change::AddPlayer(addition) => {
+                    numbers.push((format!("history[{i}].rating"), addition.rating))
+                }
+                Change::SetRating(set) => {
+                    numbers.push((format!("history[{i}].rating"), set.rating))
+                }
+                Change::AdjustAlpha(adjust) => {
+                    numbers.push((format!("history[{i}].value"), adjust.value))
+                }
+                Change::Play(play) => {
+                    if let Some(games) = play.fractional_games {
+                        numbers.push((format!("history[{i}].fractional_games"), games))
+                    }
+                }
+                Change::SeasonBoundary(_) => {}
+            }
+        }
+
+        numbers.retain(|(_, value)| !value.is_finite());
+        numbers
+    }
+
+    /// Fails with the first of [`Data::non_finite_numbers`]
+    pub fn check_finite(&self) -> Result<(), String> {
+        match self.non_finite_numbers().first() {
+            Some((location, value)) => Err(format!(
+                "{location} is {value}, only finite numbers are allowed"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Same as [`Data::evaluate`], but also measures where the time is spent
+    pub fn evaluate_timed(&self) -> (Evaluation, EvalTimings) {
+        let start = Instant::now();
+        let mut timings = EvalTimings::default();
+        let mut eval = Evaluation::new(&self.config);
+
+        for change in &self.history {
+            let change_start = Instant::now();
+            eval.change(change, &self.config);
+
+            let elapsed = change_start.elapsed();
+            match change {
+                Change::Play(_) => timings.plays += elapsed,
+                _ => timings.other_changes += elapsed,
+            }
+            timings.changes += 1;
+        }
+
+        if self.config.auto_center {
+            let center_start = Instant::now();
+            eval.center();
+            timings.centering = center_start.elapsed();
+        }
+
+        timings.total = start.elapsed();
+
+        (eval, timings)
+    }
+
+    /// The evaluation from the start of the last season named `name`, `None` if there is no such season
+    ///
+    /// Every player is re-seeded at the average of the ratings at the boundary, so the season standings only reflect games from the start of the season.
+    pub fn evaluate_season(&self, name: &str) -> Option<Evaluation> {
+        let start = self.history.iter().rposition(
+            |change| matches!(change, Change::SeasonBoundary(boundary) if boundary.name == name),
+        )?;
+
+        let mut eval = Evaluation::new(&self.config);
+        for change in &self.history[..start] {
+            eval.change(change, &self.config);
+        }
+
+        if !eval.ratings.is_empty() {
+            let seed = eval.average_rating();
+            eval.ratings.values_mut().for_each(|rating| *rating = seed);
+        }
+        eval.games_played.values_mut().for_each(|games| *games = 0);
+
+        for change in &self.history[start..] {
+            eval.change(change, &self.config);
+        }
+
+        if self.config.auto_center {
+            eval.center();
+        }
+
+        Some(eval)
+    }
+
+    /// Players mentioned by a change after the latest season boundary, or if there is none, by a change dated on or after `since`
+    ///
+    /// Without seasons and `since`, every player is active. Undated changes, such as additions, only count after a season boundary.
+    pub fn active_players(&self, since: Option<chrono::NaiveDate>) -> BTreeSet<String> {
+        let season_start = self
+            .history
+            .iter()
+            .rposition(|change| matches!(change, Change::SeasonBoundary(_)));
+
+        match (season_start, since) {
+            (Some(start), _) => player_names(&self.history[start..]),
+            (None, Some(since)) => player_names(
+                self.history
+                    .iter()
+                    .filter(|change| change.date().is_some_and(|date| date >= since)),
+            ),
+            (None, None) => self.player_names(),
+        }
+    }
+
+    /// The evaluation after only the first `count` changes of the history, e.g. to bisect a problem
+    pub fn evaluate_up_to(&self, count: usize) -> Evaluation {
+        let mut eval = Evaluation::new(&self.config);
+
+        for change in self.history.iter().take(count) {
+            eval.change(change, &self.config);
+        }
+
+        if self.config.auto_center {
+            eval.center();
+        }
+
+        eval
+    }
+
+    /// The evaluation after the changes before the first one dated on or after `date`
+    pub fn evaluate_before(&self, date: chrono::NaiveDate) -> Evaluation {
+        self.evaluate_up_to(self.cutoff_at(date))
+    }
+
+    /// Index of the first change dated on or after `date`, or the length of the history
+    fn cutoff_at(&self, date: chrono::NaiveDate) -> usize {
+        self.history
+            .iter()
+            .position(|change| change.date().is_some_and(|d| d >= date))
+            .unwrap_or(self.history.len())
+    }
+
+    /// Display rating changes since `date`, biggest gain first
+    ///
+    /// Players added after `date` are left out, as they have no rating to compare to.
+    pub fn movers(&self, date: chrono::NaiveDate) -> Vec<(String, f64)> {
+        let start = self.evaluate_before(date);
+        let end = self.evaluate();
+
+        let mut movers: Vec<(String, f64)> = end
+            .ratings
+            .iter()
+            .filter_map(|(player, rating)| {
+                let start_rating = start.ratings.get(player)?;
+                let delta = self.config.rating_to_display(*rating)
+                    - self.config.rating_to_display(*start_rating);
+                Some((player.clone(), delta))
+            })
+            .collect();
+
+        movers.sort_unstable_by(|(player_a, delta_a), (player_b, delta_b)| {
+            delta_b
+                .partial_cmp(delta_a)
+                .unwrap()
+                .then_with(|| player_a.cmp(player_b))
+        });
+        movers
+    }
+
+    /// The evaluation as if `change` was appended to the history, without modifying it
+    pub fn preview(&self, change: &Change) -> Evaluation {
+        let mut eval = Evaluation::new(&self.config);
+
+        for change in self.history.iter().chain([change]) {
+            eval.change(change, &self.config);
+        }
+
+        if self.config.auto_center {
+            eval.center();
+        }
+
+        eval
+    }
+
+    pub fn add_player(&mut self, name: String, rating: f64) {
+        self.history.push(Change::AddPlayer(AddPlayer {
+            name,
+            rating,
+            games_played: 0,
+        }));
+    }
+
+    /// Fails without adding the player if the spread is broken, see [`Config::try_rating_from_display`]
+    pub fn add_player_display(&mut self, name: String, display: f64) -> Result<(), String> {
+        let rating = self.config.try_rating_from_display(display)?;
+        self.add_player(name, rating);
+        Ok(())
+    }
+
+    /// Adds the players of `name,elo` rows, an ELO of `center` becomes the base rating and `k` ELO points one spread
+    ///
+    /// A `name,elo` header is skipped. Nothing is added if any row is invalid. Returns the number of players added.
+    pub fn import_elo(&mut self, csv: &str, center: f64, k: f64) -> Result<usize, Box<dyn Error>> {
+        let mut players = Vec::new();
+
+        for (i, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (i == 0 && line.eq_ignore_ascii_case("name,elo")) {
+                continue;
+            }
+
+            let Some((name, elo)) = line.rsplit_once(',') else {
+                return Err(format!("line {}: expected 'name,elo'", i + 1).into());
+            };
+            let elo: f64 = match elo.trim().parse() {
+                Ok(elo) if f64::is_finite(elo) => elo,
+                _ => return Err(format!("line {}: '{}' is not a number", i + 1, elo.trim()).into()),
+            };
+
+            let name = name.trim();
+            check_name(name).map_err(|err| format!("line {}: {err}", i + 1))?;
+
+            players.push((name.to_owned(), elo));
+        }
+
+        self.config.check_spread()?;
+
+        let count = players.len();
+        for (name, elo) in players {
+            let display = self.config.base_rating + (elo - center) / k * self.config.spread;
+            self.add_player_display(name, display)?;
+        }
+
+        Ok(count)
+    }
+
+    /// The internal rating a new player gets if none is specified, see [`NewPlayerPolicy`]
+    pub fn new_player_rating(&self) -> f64 {
+        let base = self.config.rating_from_display(self.config.base_rating);
+
+        match self.config.new_player_policy {
+            NewPlayerPolicy::Base => base,
+            NewPlayerPolicy::Average => {
+                let eval = self.evaluate();
+
+                if eval.ratings.is_empty() {
+                    base
+                } else {
+                    eval.average_rating()
+                }
+            }
+        }
+    }
+
+    pub fn set_rating(&mut self, player: String, rating: f64) {
+        self.history
+            .push(Change::SetRating(SetRating { player, rating }));
+    }
+
+    /// Fails without setting the rating if the spread is broken, see [`Config::try_rating_from_display`]
+    pub fn set_rating_display(&mut self, player: String, display: f64) -> Result<(), String> {
+        let rating = self.config.try_rating_from_display(display)?;
+        self.set_rating(player, rating);
+        Ok(())
+    }
+
+    /// Outcomes are stored in canonical order, see [`Play::canonicalize`]
+    pub fn play(&mut self, mut play: Play) {
+        play.canonicalize();
+        self.history.push(Change::Play(play));
+    }
+
+    pub fn start_season(&mut self, name: String, date: Option<chrono::NaiveDate>) {
+        self.history
+            .push(Change::SeasonBoundary(SeasonBoundary { name, date }));
+    }
+
+    pub fn adjust_α(&mut self, new: f64) {
+        self.adjust_α_with(AdjustAlpha {
+            value: new,
+            ..Default::default()
+        });
+    }
+
+    /// Same as [`Data::adjust_α`], but with a date and reason
+    pub fn adjust_α_with(&mut self, adjust: AdjustAlpha) {
+        self.history.push(Change::AdjustAlpha(adjust));
+    }
+
+    pub fn adjust_score_multiplier(&mut self, new: f64) {
+        self.adjust_α(self.config.α_from_display(new));
+    }
+
+    /// Replaces the changes before the first one dated on or after `date` with additions of the players at their ratings and games played at that point
+    ///
+    /// Ratings, α and games played evaluate the same as before. α adjustments and season boundaries are kept after the additions, in their order,
+    /// but seasons started before `date` lose the games before it, see [`Data::seasons_cut_by_compaction`].
+    /// Returns the number of replaced changes.
+    pub fn compact_before(&mut self, date: chrono::NaiveDate) -> usize {
+        let cutoff = self.cutoff_at(date);
+        self.compact_up_to(cutoff);
+
+        cutoff
+    }
+
+    /// Removes the first change dated after `date` and everything following it, returns the number of removed changes
+    ///
+    /// Undated changes, such as additions of players, are kept if they come before it. Unlike [`Data::compact_before`], ratings return to what they were.
+    pub fn rollback_to(&mut self, date: chrono::NaiveDate) -> usize {
+        let cutoff = match date.succ_opt() {
+            Some(next) => self.cutoff_at(next),
+            None => self.history.len(),
+        };
+
+        self.history.drain(cutoff..).count()
+    }
+
+    /// Names of the seasons started before `date` with plays before it, whose standings would lose those plays by [`Data::compact_before`]
+    pub fn seasons_cut_by_compaction(&self, date: chrono::NaiveDate) -> Vec<&str> {
+        let compacted = &self.history[..self.cutoff_at(date)];
+
+        compacted
+            .iter()
+            .enumerate()
+            .filter_map(|(i, change)| match change {
+                Change::SeasonBoundary(boundary)
+                    if compacted[i..]
+                        .iter()
+                        .any(|change| matches!(change, Change::Play(_))) =>
+                {
+                    Some(boundary.name.as_str())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// A copy with the same config, whose history only adds the players at their current ratings
+    ///
+    /// It evaluates to the same ratings and α, but without any games played or seasons, e.g. to start a new season from.
+    pub fn snapshot(&self) -> Data {
+        let mut snapshot = Data {
+            config: self.config.clone(),
+            history: self.history.clone(),
+            ..Default::default()
+        };
+        snapshot.compact_up_to(snapshot.history.len());
+
+        snapshot.history.retain_mut(|change| match change {
+            Change::AddPlayer(addition) => {
+                addition.games_played = 0;
+                true
+            }
+            Change::SeasonBoundary(_) => false,
+            _ => true,
+        });
+
+        snapshot
+    }
+
+    fn compact_up_to(&mut self, cutoff: usize) {
+        // Not centered, later changes such as additions of players are relative to the uncentered ratings
+        let mut eval = Evaluation::new(&self.config);
+        for change in &self.history[..cutoff] {
+            eval.change(change, &self.config);
+        }
+
+        let mut ratings: Vec<(String, f64)> = eval.ratings.into_iter().collect();
+        ratings.sort_unstable_by(|(player_a, _), (player_b, _)| player_a.cmp(player_b));
+
+        let mut compacted: Vec<Change> = ratings
+            .into_iter()
+            .map(|(name, rating)| {
+                Change::AddPlayer(AddPlayer {
+                    games_played: eval.games_played[&name],
+                    name,
+                    rating,
+                })
+            })
+            .collect();
+
+        // α only changes by adjustments, so the last one kept sets the same α
+        compacted.extend(
+            self.history[..cutoff]
+                .iter()
+                .filter(|change| {
+                    matches!(change, Change::AdjustAlpha(_) | Change::SeasonBoundary(_))
+                })
+                .cloned(),
+        );
+
+        self.history.splice(..cutoff, compacted);
+    }
+
+    /// Every change referencing `player`, with their rating before and after it
+    pub fn rating_events(&self, player: &str) -> Vec<RatingEvent<'_>> {
+        let mut events = Vec::new();
+        let mut eval = Evaluation::new(&self.config);
+
+        for (i, change) in self.history.iter().enumerate() {
+            let before = eval.ratings.get(player).copied();
+            eval.change(change, &self.config);
+
+            if change.references(player) {
+                events.push(RatingEvent {
+                    history_index: i,
+                    change,
+                    before,
+                    after: eval.ratings[player],
+                });
+            }
+        }
+
+        events
+    }
+
+    /// The changes from index `start` up to but excluding `end`, i.e. those between [`Data::evaluate_up_to`] `start` and `end`
+    ///
+    /// Panics like slicing if `start > end` or `end` is past the end of the history.
+    pub fn changes_between(&self, start: usize, end: usize) -> &[Change] {
+        &self.history[start..end]
+    }
+
+    /// The changes of [`Data::changes_between`] which moved the rating of `player`, with their rating before and after
+    ///
+    /// Adding the player counts as a move, a play with a net score of 0 against equal opponents doesn't.
+    pub fn rating_moves_between(
+        &self,
+        player: &str,
+        start: usize,
+        end: usize,
+    ) -> Vec<RatingEvent<'_>> {
+        let mut eval = Evaluation::new(&self.config);
+        for change in self.changes_between(0, start) {
+            eval.change(change, &self.config);
+        }
+
+        let mut moves = Vec::new();
+        for (i, change) in (start..).zip(self.changes_between(start, end)) {
+            let before = eval.ratings.get(player).copied();
+            eval.change(change, &self.config);
+
+            match eval.ratings.get(player) {
+                Some(&after) if before != Some(after) => moves.push(RatingEvent {
+                    history_index: i,
+                    change,
+                    before,
+                    after,
+                }),
+                _ => {}
+            }
+        }
+
+        moves
+    }
+
+    /// One block character per rating event of `player`, scaled between their lowest and highest rating
+    pub fn sparkline(&self, player: &str) -> String {
+        let ratings: Vec<f64> = self
+            .rating_events(player)
+            .iter()
+            .map(|event| event.after)
+            .collect();
+
+        sparkline(&ratings)
+    }
+
+    /// Dates don't affect the order of changes, so ratings aren't affected
+    pub fn set_date(
+        &mut self,
+        index: usize,
+        date: chrono::NaiveDate,
+    ) -> Result<(), Box<dyn Error>> {
+        let len = self.history.len();
+
+        self.history
+            .get_mut(index)
+            .ok_or_else(|| format!("index {index} is out of range, history has {len} changes"))?
+            .set_date(date)
+    }
+
+    /// Evaluations after each date with activity
+    ///
+    /// A time point ends at a dated change if the next dated change has a different date or session. Undated changes don't end time points.
+    pub fn rating_timeline(&self) -> Vec<(TimePoint, Evaluation)> {
+        self.timeline(self.config.auto_center)
+    }
+
+    /// Average internal rating after each date with activity, see [`Data::rating_timeline`]
+    ///
+    /// Ratings aren't centered, even with `auto_center`. Plays keep the sum of the ratings, unless clamped or with per-player α, so only additions and set ratings should move the average.
+    pub fn inflation(&self) -> Vec<(TimePoint, f64)> {
+        self.timeline(false)
+            .into_iter()
+            .map(|(point, eval)| (point, eval.average_rating()))
+            .collect()
+    }
+
+    fn timeline(&self, center: bool) -> Vec<(TimePoint, Evaluation)> {
+        let mut timeline = Vec::new();
+        let mut eval = Evaluation::new(&self.config);
+
+        for (i, change) in self.history.iter().enumerate() {
+            eval.change(change, &self.config);
+
+            let Some(time_point) = change.time_point() else {
+                continue;
+            };
+
+            if self.history[i + 1..].iter().find_map(Change::time_point) != Some(time_point) {
+                let mut point = eval.clone();
+                if center {
+                    point.center();
+                }
+
+                timeline.push((time_point, point));
+            }
+        }
+
+        timeline
+    }
+
+    /// The current standings as a GitHub-flavored Markdown table with rank, player, rating and games columns
+    ///
+    /// Only players with at least `ranking_min_games` games are listed, tied players share their rank.
+    pub fn standings_markdown(&self) -> String {
+        let eval = self.evaluate();
+        let mut leaderboard = eval.leaderboard(self.config.ranking_min_games);
+        leaderboard.sort_by(|(player_a, rating_a), (player_b, rating_b)| {
+            rating_b
+                .partial_cmp(rating_a)
+                .unwrap()
+                .then_with(|| player_a.cmp(player_b))
+        });
+
+        let mut table = "| Rank | Player | Rating | Games |\n|---:|:---|---:|---:|\n".to_owned();
+        for (player, rating) in &leaderboard {
+            let rank = 1 + leaderboard
+                .iter()
+                .filter(|(_, other)| other > rating)
+                .count();
+            let display = self
+                .config
+                .display
+                .format(self.config.rating_to_listed_display(*rating));
+
+            table += &format!(
+                "| {rank} | {} | {} | {} |\n",
+                player.replace('|', "\\|"),
+                display.trim(),
+                eval.games_played[*player]
+            );
+        }
+
+        table
+    }
+
+    /// Delimiter separated display ratings over time, see [`Data::rating_timeline`]
+    ///
+    /// The options should pass [`TableOptions::check`].
+    pub fn ratings_table(&self, options: &TableOptions) -> String {
+        let timeline = self.rating_timeline();
+
+        let players: Vec<&str> = match &options.players {
+            Some(players) => players.iter().map(|player| player.as_str()).collect(),
+            None => timeline
+                .iter()
+                .flat_map(|(_, eval)| eval.ratings.keys())
+                .map(|player| player.as_str())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+        };
+
+        let delimiter = options.delimiter.to_string();
+        let mut table = match options.layout {
+            Layout::Wide => ["datum"]
+                .iter()
+                .chain(&players)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(&delimiter),
+            Layout::Tidy => ["datum", "player", "rating"].join(&delimiter),
+        };
+        table += "\n";
+
+        table + &self.ratings_rows(&timeline, &players, options)
+    }
+
+    /// Display ratings of `player` after each date with activity since they were added, with datum and rating columns
+    pub fn player_timeline_table(&self, player: &str) -> String {
+        let timeline: Vec<(TimePoint, Evaluation)> = self
+            .rating_timeline()
+            .into_iter()
+            .filter(|(_, eval)| eval.ratings.contains_key(player))
+            .collect();
+
+        "datum\trating\n".to_owned()
+            + &self.ratings_rows(&timeline, &[player], &TableOptions::default())
+    }
+
+    /// Rows of [`Data::ratings_table`] missing from the end of `existing`, a table exported earlier with the same options
+    ///
+    /// The columns of a wide table are kept, so players added since are missing from the new rows and `options.players` is ignored. Fails if the rows of `existing` aren't the current ones, e.g. because the history was edited or a play was added on the last exported date.
+    pub fn ratings_table_append(
+        &self,
+        options: &TableOptions,
+        existing: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let layout = options.layout;
+        let mismatch =
+            || "the existing table doesn't match the history, export it again without appending";
+
+        let mut lines = existing.lines();
+        let header: Vec<&str> = lines
+            .next()
+            .unwrap_or_default()
+            .split(options.delimiter)
+            .collect();
+        let players = match layout {
+            Layout::Wide if header.first() == Some(&"datum") => header[1..].to_vec(),
+            Layout::Tidy if header == ["datum", "player", "rating"] => Vec::new(),
+            _ => return Err("the existing table has a different layout".into()),
+        };
+
+        let rows: Vec<&str> = lines.collect();
+        let mut points = 0;
+        let mut last = None;
+        for row in &rows {
+            let datum = row.split(options.delimiter).next().unwrap_or_default();
+            if last != Some(datum) {
+                points += 1;
+                last = Some(datum);
+            }
+        }
+
+        let timeline = self.rating_timeline();
+        if points > timeline.len() {
+            return Err(mismatch().into());
+        }
+
+        let players = match layout {
+            Layout::Wide => players,
+            Layout::Tidy => timeline
+                .iter()
+                .flat_map(|(_, eval)| eval.ratings.keys())
+                .map(|player| player.as_str())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+        };
+
+        if !self
+            .ratings_rows(&timeline[..points], &players, options)
+            .lines()
+            .eq(rows)
+        {
+            return Err(mismatch().into());
+        }
+
+        Ok(self.ratings_rows(&timeline[points..], &players, options))
+    }
+
+    fn ratings_rows(
+        &self,
+        timeline: &[(TimePoint, Evaluation)],
+        players: &[&str],
+        options: &TableOptions,
+    ) -> String {
+        let delimiter = options.delimiter.to_string();
+        let format = |rating: f64| {
+            let formatted = format!("{:.1}", self.config.rating_to_listed_display(rating));
+            if options.decimal_comma {
+                formatted.replace('.', ",")
+            } else {
+                formatted
+            }
+        };
+        let mut table = String::new();
+
+        match options.layout {
+            Layout::Wide => {
+                for (date, eval) in timeline {
+                    let cells = players
+                        .iter()
+                        .map(|player| match eval.ratings.get(*player) {
+                            Some(rating) => format(*rating),
+                            None => String::new(),
+                        });
+
+                    table += &[self.config.format_time_point(*date)]
+                        .into_iter()
+                        .chain(cells)
+                        .collect::<Vec<_>>()
+                        .join(&delimiter);
+                    table += "\n";
+                }
+            }
+            Layout::Tidy => {
+                for (date, eval) in timeline {
+                    for player in players {
+                        if let Some(rating) = eval.ratings.get(*player) {
+                            table += &[
+                                self.config.format_time_point(*date),
+                                player.to_string(),
+                                format(*rating),
+                            ]
+                            .join(&delimiter);
+                            table += "\n";
+                        }
+                    }
+                }
+            }
+        }
+
+        table
+    }
+
+    /// Number of changes on each date, undated changes are left out
+    pub fn activity_dates(&self) -> BTreeMap<chrono::NaiveDate, usize> {
+        let mut dates = BTreeMap::new();
+
+        for date in self.history.iter().filter_map(Change::date) {
+            *dates.entry(date).or_insert(0) += 1;
+        }
+
+        dates
+    }
+
+    /// Total number of games played by each pair of players, pairs are ordered by name
+    pub fn games_between(&self) -> BTreeMap<(String, String), usize> {
+        let mut games = BTreeMap::new();
+
+        for change in &self.history {
+            let Change::Play(play) = change else {
+                continue;
+            };
+
+            for i in 0..3 {
+                for j in i + 1..3 {
+                    let first = &play.outcomes[i].player;
+                    let second = &play.outcomes[j].player;
+                    let pair = if first <= second {
+                        (first.clone(), second.clone())
+                    } else {
+                        (second.clone(), first.clone())
+                    };
+
+                    *games.entry(pair).or_insert(0) += play.game_count;
+                }
+            }
+        }
+
+        games
+    }
+
+    /// Results of the plays in which both `a` and `b` played, grouped into buckets named by `group_by`
+    ///
+    /// Buckets are "all" if not grouped, otherwise the year of the play, or "unknown" for undated ones.
+    pub fn head_to_head(
+        &self,
+        a: &str,
+        b: &str,
+        group_by: GroupBy,
+    ) -> BTreeMap<String, HeadToHead> {
+        let mut buckets: BTreeMap<String, HeadToHead> = BTreeMap::new();
+
+        for change in &self.history {
+            let Change::Play(play) = change else {
+                continue;
+            };
+
+            let score = |player| {
+                play.outcomes
+                    .iter()
+                    .find(|outcome| outcome.player == player)
+                    .map(|outcome| outcome.score)
+            };
+            let (Some(score_a), Some(score_b)) = (score(a), score(b)) else {
+                continue;
+            };
+
+            let bucket = match (group_by, change.date()) {
+                (GroupBy::None, _) => "all".to_owned(),
+                (GroupBy::Year, Some(date)) => chrono::Datelike::year(&date).to_string(),
+                (GroupBy::Year, None) => "unknown".to_owned(),
+            };
+
+            let entry = buckets.entry(bucket).or_default();
+            entry.plays += 1;
+            entry.games += play.game_count;
+            entry.scores[0] += score_a;
+            entry.scores[1] += score_b;
+        }
+
+        buckets
+    }
+
+    /// Total score of the first player minus the second one's over the plays they were both part of, pairs are ordered by name
+    pub fn pairwise_balances(&self) -> BTreeMap<(String, String), i64> {
+        let mut balances = BTreeMap::new();
+
+        for change in &self.history {
+            let Change::Play(play) = change else {
+                continue;
+            };
+
+            for i in 0..3 {
+                for j in i + 1..3 {
+                    let (first, second) = (&play.outcomes[i], &play.outcomes[j]);
+                    let (first, second) = if first.player <= second.player {
+                        (first, second)
+                    } else {
+                        (second, first)
+                    };
+
+                    *balances
+                        .entry((first.player.clone(), second.player.clone()))
+                        .or_insert(0) += first.score - second.score;
+                }
+            }
+        }
+
+        balances
+    }
+
+    /// Cycles of at most `max_len` players in which each player has a positive balance against the next, see [`Data::pairwise_balances`]
+    ///
+    /// Each cycle is listed once, starting with its first player by name, the last player beats the first one.
+    pub fn intransitive_cycles(&self, max_len: usize) -> Vec<Vec<String>> {
+        let mut beats: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        let balances = self.pairwise_balances();
+        for ((first, second), balance) in &balances {
+            match balance.cmp(&0) {
+                std::cmp::Ordering::Greater => beats.entry(first).or_default().push(second),
+                std::cmp::Ordering::Less => beats.entry(second).or_default().push(first),
+                std::cmp::Ordering::Equal => {}
+            }
+        }
+
+        fn extend<'p>(
+            beats: &BTreeMap<&'p str, Vec<&'p str>>,
+            path: &mut Vec<&'p str>,
+            max_len: usize,
+            cycles: &mut Vec<Vec<String>>,
+        ) {
+            let start = path[0];
+            let last = path[path.len() - 1];
+
+            for &next in beats.get(last).into_iter().flatten() {
+                if next == start && path.len() >= 3 {
+                    cycles.push(path.iter().map(|player| player.to_string()).collect());
+                } else if next > start && !path.contains(&next) && path.len() < max_len {
+                    path.push(next);
+                    extend(beats, path, max_len, cycles);
+                    path.pop();
+                }
+            }
+        }
+
+        let mut cycles = Vec::new();
+        for &start in beats.keys() {
+            extend(&beats, &mut vec![start], max_len, &mut cycles);
+        }
+
+        cycles.sort_unstable();
+        cycles
+    }
+
+    /// Graphviz DOT graph of who played whom, edges are labeled with the number of games
+    pub fn to_dot(&self) -> String {
+        let quote = |name: &str| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+
+        let mut players: Vec<String> = self.evaluate().ratings.into_keys().collect();
+        players.sort_unstable();
+
+        let mut dot = String::from("graph ultira {\n");
+
+        for player in players {
+            dot += &format!("    {};\n", quote(&player));
+        }
+
+        for ((first, second), games) in self.games_between() {
+            dot += &format!(
+                "    {} -- {} [label = \"{games}\", weight = {games}];\n",
+                quote(&first),
+                quote(&second)
+            );
+        }
+
+        dot += "}\n";
+        dot
+    }
+
+    /// Lists problems which would make the history fail or misbehave during evaluation
+    pub fn check(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut players: HashSet<&str> = HashSet::new();
+
+        for (i, change) in self.history.iter().enumerate() {
+            match change {
+                Change::AddPlayer(addition) => {
+                    players.insert(&addition.name);
+
+                    if check_name(&addition.name).is_err() {
+                        problems.push(Problem {
+                            kind: ProblemKind::EmptyName,
+                            history_index: Some(i),
+                            message: format!("player '{}' has an empty name", addition.name),
+                        });
+                    }
+                }
+                Change::Play(play) => {
+                    for outcome in &play.outcomes {
+                        if !players.contains(outcome.player.as_str()) {
+                            problems.push(Problem {
+                                kind: ProblemKind::UnknownPlayer,
+                                history_index: Some(i),
+                                message: format!(
+                                    "player '{}' is referenced before being added",
+                                    outcome.player
+                                ),
+                            });
+                        }
+                    }
+                }
+                Change::SetRating(set) => {
+                    if !players.contains(set.player.as_str()) {
+                        problems.push(Problem {
+                            kind: ProblemKind::UnknownPlayer,
+                            history_index: Some(i),
+                            message: format!(
+                                "player '{}' is referenced before being added",
+                                set.player
+                            ),
+                        });
+                    }
+                }
+                Change::AdjustAlpha(_) | Change::SeasonBoundary(_) => {}
+            }
+        }
+
+        problems
+    }
+
+    /// Lists rating-affecting changes after which the sum of all ratings moved by more than `eps`
+    ///
+    /// Zero-sum scores with uniform α conserve the sum, so this catches invalid scores and non-uniform α.
+    pub fn check_conservation(&self, eps: f64) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut eval = Evaluation::new(&self.config);
+
+        for (i, change) in self.history.iter().enumerate() {
+            let before = eval.rating_sum();
+            eval.change(change, &self.config);
+            let after = eval.rating_sum();
+
+            if matches!(change, Change::Play(_)) && (after - before).abs() > eps {
+                problems.push(Problem {
+                    kind: ProblemKind::NotConserved,
+                    history_index: Some(i),
+                    message: format!("sum of ratings changed by {}", after - before),
+                });
+            }
+        }
+
+        problems
+    }
+
+    /// Evaluates the history twice and reports the first player, by name, whose ratings aren't bit-identical
+    ///
+    /// Every evaluation iterates its maps in a different order, so this catches results depending on that order.
+    pub fn check_determinism(&self) -> Vec<Problem> {
+        let first = self.evaluate();
+        let second = self.evaluate();
+
+        let mut players: Vec<&String> = first.ratings.keys().chain(second.ratings.keys()).collect();
+        players.sort_unstable();
+        players.dedup();
+
+        let divergent = players.into_iter().find_map(|player| {
+            let (a, b) = (first.ratings.get(player), second.ratings.get(player));
+            (a.map(|a| a.to_bits()) != b.map(|b| b.to_bits()))
+                .then(|| format!("rating of '{player}' evaluated to both {a:?} and {b:?}"))
+        });
+        let divergent = divergent.or_else(|| {
+            (first.α.to_bits() != second.α.to_bits())
+                .then(|| format!("α evaluated to both {} and {}", first.α, second.α))
+        });
+
+        divergent
+            .map(|message| Problem {
+                kind: ProblemKind::NonDeterministic,
+                history_index: None,
+                message,
+            })
+            .into_iter()
+            .collect()
+    }
+
+    /// Lists plays whose scores don't sum to zero, files written before the sum was validated may contain them
+    pub fn check_zero_sum(&self) -> Vec<Problem> {
+        self.history
+            .iter()
+            .enumerate()
+            .filter_map(|(i, change)| match change {
+                Change::Play(play) => {
+                    let sum = play.outcomes.iter().map(|o| o.score).sum::<i64>();
+                    (sum != 0).then(|| Problem {
+                        kind: ProblemKind::NonZeroSum,
+                        history_index: Some(i),
+                        message: format!("scores of the play sum to {sum}"),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Lists players added more than once, re-adding a player silently overrides their rating
+    pub fn check_duplicate_players(&self) -> Vec<Problem> {
+        let mut additions: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+
+        for (i, change) in self.history.iter().enumerate() {
+            if let Change::AddPlayer(addition) = change {
+                additions.entry(&addition.name).or_default().push(i);
+            }
+        }
+
+        additions
+            .into_iter()
+            .filter(|(_, indices)| indices.len() > 1)
+            .map(|(name, indices)| Problem {
+                kind: ProblemKind::DuplicateAddPlayer,
+                history_index: Some(indices[1]),
+                message: format!(
+                    "player '{name}' is added multiple times, at history indices {}",
+                    indices
+                        .iter()
+                        .map(|i| i.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            })
+            .collect()
+    }
+
+    /// Removes changes equal to the previous one up to `eps`, e.g. a play entered twice by accident
+    ///
+    /// Returns the number of removed changes.
+    pub fn dedup_adjacent(&mut self, eps: f64) -> usize {
+        let len = self.history.len();
+        self.history
+            .dedup_by(|next, previous| next.approx_eq(previous, eps));
+
+        len - self.history.len()
+    }
+
+    /// Every name mentioned by the history, without evaluating it
+    ///
+    /// There is no way to remove a player, so these are the players of the evaluation for valid files. Names renamed away are gone from the history and aren't listed.
+    pub fn player_names(&self) -> BTreeSet<String> {
+        player_names(&self.history)
+    }
+
+    /// Indices of the changes mentioning `player`, see [`Change::references`]
+    pub fn references(&self, player: &str) -> Vec<usize> {
+        self.history
+            .iter()
+            .enumerate()
+            .filter(|(_, change)| change.references(player))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Number of changes referencing each player, per [`Change::variant_name`]
+    ///
+    /// The counts of a player sum to the length of their [`Data::references`].
+    pub fn participation(&self) -> HashMap<String, BTreeMap<&'static str, usize>> {
+        let mut participation: HashMap<String, BTreeMap<&'static str, usize>> = HashMap::new();
+
+        for change in &self.history {
+            for player in player_names([change]) {
+                *participation
+                    .entry(player)
+                    .or_default()
+                    .entry(change.variant_name())
+                    .or_insert(0) += 1;
+            }
+        }
+
+        participation
+    }
+
+    /// Renaming a player to their own name does nothing
+    pub fn rename(&mut self, old_name: &str, new_name: &str) {
+        if old_name == new_name {
+            return;
+        }
+
+        if let Some(multiplier) = self.config.player_alpha.remove(old_name) {
+            self.config
+                .player_alpha
+                .insert(new_name.to_owned(), multiplier);
+        }
+
+        for elem in &mut self.history {
+            match elem {
+                Change::AddPlayer(p) => {
+                    if p.name == old_name {
+                        p.name = new_name.to_owned();
+                    }
+                }
+                Change::Play(p) => {
+                    if p.outcomes.iter().any(|outcome| outcome.player == old_name) {
+                        for outcome in &mut p.outcomes {
+                            if outcome.player == old_name {
+                                outcome.player = new_name.to_owned();
+                            }
+                        }
+                        // The same order as a play recorded with the new name
+                        p.canonicalize();
+                    }
+                }
+                Change::SetRating(p) => {
+                    if p.player == old_name {
+                        p.player = new_name.to_owned();
+                    }
+                }
+                Change::AdjustAlpha(_) | Change::SeasonBoundary(_) => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    /// Index of the offending change, if the problem is tied to one
+    pub history_index: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.history_index {
+            Some(i) => write!(f, "history[{i}]: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ProblemKind {
+    UnknownPlayer,
+    NotConserved,
+    DuplicateAddPlayer,
+    NonZeroSum,
+    EmptyName,
+    NonDeterministic,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Config {
+    pub spread: f64,
+    pub base_rating: f64,
+    pub starting_alpha: f64,
+    #[serde(default)]
+    pub new_player_policy: NewPlayerPolicy,
+    /// Per-player multipliers of α, players not listed have a multiplier of 1
+    #[serde(default)]
+    pub player_alpha: HashMap<String, f64>,
+    /// Players with fewer games are hidden from the ratings listing by default
+    #[serde(default)]
+    pub ranking_min_games: usize,
+    /// Plays with more games are rejected as likely typos
+    #[serde(default = "default_max_game_count")]
+    pub max_game_count: usize,
+    /// Largest change of a player's display rating a single play can cause, unlimited if unset
+    ///
+    /// Clamped plays no longer keep the sum of the ratings unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rating_change_per_play: Option<f64>,
+    /// Lowest display rating shown, unlimited if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating_floor: Option<f64>,
+    /// Highest display rating shown, unlimited if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating_ceiling: Option<f64>,
+    /// Also clamp the internal ratings to the floor and ceiling after every change, not only the displayed ones
+    ///
+    /// This distorts the math: clamped players keep playing from the clamped rating, and the sum of the ratings is no longer kept.
+    #[serde(default)]
+    pub clamp_internal_ratings: bool,
+    /// Round the stored internal ratings of added players and set ratings to this many decimals when writing, full precision if unset
+    ///
+    /// Shorter numbers keep diffs of the file readable. Evaluation is unaffected, but rounding to 4 decimals can move a display rating by up to 0.00005 spreads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_precision: Option<u32>,
+    /// Shift the evaluated ratings so that their average is 0 (i.e. the base rating when displayed)
+    ///
+    /// Plays only depend on rating differences, so this only affects the absolute level of the ratings.
+    #[serde(default)]
+    pub auto_center: bool,
+    /// If set, every command which may modify the file is logged here before being executed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<PathBuf>,
+    /// Presentation defaults, used when the corresponding flags aren't given
+    #[serde(default)]
+    pub display: DisplayConfig,
+    /// chrono format string of dates in listings and exports, e.g. "%d.%m.%Y", ISO 8601 by default
+    ///
+    /// Dates are always stored and parsed as YYYY-MM-DD.
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    /// Answers accepted by confirmation prompts, compared ignoring case and surrounding whitespace, e.g. "igen"
+    #[serde(default = "default_confirm_words")]
+    pub confirm_words: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            spread: 50.0,
+            base_rating: 100.0,
+            starting_alpha: 0.02,
+            new_player_policy: NewPlayerPolicy::default(),
+            player_alpha: HashMap::new(),
+            ranking_min_games: 0,
+            max_game_count: default_max_game_count(),
+            max_rating_change_per_play: None,
+            rating_floor: None,
+            rating_ceiling: None,
+            clamp_internal_ratings: false,
+            store_precision: None,
+            auto_center: false,
+            audit_log: None,
+            display: DisplayConfig::default(),
+            date_format: default_date_format(),
+            confirm_words: default_confirm_words(),
+        }
+    }
+}
+
+fn default_max_game_count() -> usize {
+    100_000
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_owned()
+}
+
+fn default_confirm_words() -> Vec<String> {
+    vec!["y".to_owned(), "yes".to_owned()]
+}
+
+/// Decides the rating of new players added without an explicit rating
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NewPlayerPolicy {
+    /// Start at the base rating
+    #[default]
+    Base,
+    /// Start at the average internal rating of the existing players
+    Average,
+}
+
+impl FromStr for NewPlayerPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base" => Ok(Self::Base),
+            "average" => Ok(Self::Average),
+            _ => Err(format!("expected 'base' or 'average', found '{s}'")),
+        }
+    }
+}
+
+impl fmt::Display for NewPlayerPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base => write!(f, "base"),
+            Self::Average => write!(f, "average"),
+        }
+    }
+}
+
+/// How ratings are presented
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// Use a comma as the decimal separator
+    pub decimal_comma: bool,
+    /// Number of decimals
+    pub precision: usize,
+    pub sort: RatingSort,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            decimal_comma: false,
+            precision: 1,
+            sort: RatingSort::default(),
+        }
+    }
+}
+
+impl DisplayConfig {
+    /// These settings with the given ones replacing the stored defaults
+    pub fn with_overrides(
+        self,
+        decimal_comma: Option<bool>,
+        precision: Option<usize>,
+        sort: Option<RatingSort>,
+    ) -> Self {
+        Self {
+            decimal_comma: decimal_comma.unwrap_or(self.decimal_comma),
+            precision: precision.unwrap_or(self.precision),
+            sort: sort.unwrap_or(self.sort),
+        }
+    }
+
+    /// Formats a difference of display ratings with its sign, aligned like [`DisplayConfig::format`]
+    pub fn format_signed(&self, delta: f64) -> String {
+        let formatted = format!(
+            "{:>+width$.precision$}",
+            delta,
+            width = self.precision + 5,
+            precision = self.precision
+        );
+
+        if self.decimal_comma {
+            formatted.replace('.', ",")
+        } else {
+            formatted
+        }
+    }
+
+    /// Formats a display rating, right aligned to fit typical ratings
+    pub fn format(&self, display: f64) -> String {
+        let formatted = format!(
+            "{:>width$.precision$}",
+            display,
+            width = self.precision + 5,
+            precision = self.precision
+        );
+
+        if self.decimal_comma {
+            formatted.replace('.', ",")
+        } else {
+            formatted
+        }
+    }
+}
+
+/// Order of the ratings listing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RatingSort {
+    /// Highest rating first
+    #[default]
+    Rating,
+    /// Alphabetically by name
+    Name,
+}
+
+impl FromStr for RatingSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rating" => Ok(Self::Rating),
+            "name" => Ok(Self::Name),
+            _ => Err(format!("expected 'rating' or 'name', found '{s}'")),
+        }
+    }
+}
+
+impl fmt::Display for RatingSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rating => write!(f, "rating"),
+            Self::Name => write!(f, "name"),
+        }
+    }
+}
+
+impl Config {
+    /// Every field with its type, current and default value, derived from the serialized config
+    ///
+    /// Optional fields which are unset both currently and by default don't serialize, so they are missing.
+    pub fn schema(&self) -> Result<String, Box<dyn Error>> {
+        let current = toml::Value::try_from(self)?;
+        let default = toml::Value::try_from(Config::default())?;
+        let (Some(current), Some(default)) = (current.as_table(), default.as_table()) else {
+            return Err("config doesn't serialize as a table".into());
+        };
+
+        let fields: BTreeSet<&String> = current.keys().chain(default.keys()).collect();
+        let describe = |value: Option<&toml::Value>| match value {
+            Some(value) => value.to_string(),
+            None => "unset".to_owned(),
+        };
+
+        let mut schema = String::new();
+        for field in fields {
+            let kind = current
+                .get(field)
+                .or_else(|| default.get(field))
+                .map_or("unknown", |value| value.type_str());
+
+            schema += &format!(
+                "{field} ({kind}) = {} (default: {})\n",
+                describe(current.get(field)),
+                describe(default.get(field))
+            );
+        }
+
+        Ok(schema)
+    }
+
+    /// Infinite or sign-flipped unless the spread is positive, see [`Config::try_rating_from_display`]
+    pub fn rating_from_display(&self, display: f64) -> f64 {
+        (display - self.base_rating) / self.spread
+    }
+
+    /// Same as [`Config::rating_from_display`], but fails instead of giving a non-finite or sign-flipped rating
+    pub fn try_rating_from_display(&self, display: f64) -> Result<f64, String> {
+        self.check_spread()?;
+
+        let rating = self.rating_from_display(display);
+        if !rating.is_finite() {
+            return Err(format!("{display} isn't a valid display rating"));
+        }
+
+        Ok(rating)
+    }
+
+    /// Fails unless the spread is a positive finite number, as display ratings can't be converted otherwise
+    pub fn check_spread(&self) -> Result<(), String> {
+        if self.spread > 0.0 && self.spread.is_finite() {
+            Ok(())
+        } else {
+            Err(format!(
+                "the spread is {}, it must be positive, fix it with config spread",
+                self.spread
+            ))
+        }
+    }
+
+    /// Fails unless the starting α is a positive finite number, ratings wouldn't move or would move backwards otherwise
+    pub fn check_starting_α(&self) -> Result<(), String> {
+        if self.starting_alpha > 0.0 && self.starting_alpha.is_finite() {
+            Ok(())
+        } else {
+            Err(format!(
+                "the starting α is {}, it must be positive",
+                self.starting_alpha
+            ))
+        }
+    }
+
+    /// Fails unless the base rating is finite
+    pub fn check_base_rating(&self) -> Result<(), String> {
+        if self.base_rating.is_finite() {
+            Ok(())
+        } else {
+            Err(format!(
+                "the base rating is {}, it must be a finite number",
+                self.base_rating
+            ))
+        }
+    }
+
+    pub fn rating_to_display(&self, rating: f64) -> f64 {
+        rating * self.spread + self.base_rating
+    }
+
+    /// The display rating as listed in standings, clamped to the rating floor and ceiling
+    ///
+    /// Differences and statistics should use the unclamped [`Config::rating_to_display`].
+    pub fn rating_to_listed_display(&self, rating: f64) -> f64 {
+        self.clamp_display(self.rating_to_display(rating))
+    }
+
+    /// Fails if the rating floor is above the rating ceiling, which would list everyone at the ceiling
+    pub fn check_display_range(&self) -> Result<(), String> {
+        match (self.rating_floor, self.rating_ceiling) {
+            (Some(floor), Some(ceiling)) if floor > ceiling => Err(format!(
+                "the rating floor {floor} is above the rating ceiling {ceiling}"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn clamp_display(&self, display: f64) -> f64 {
+        let display = match self.rating_floor {
+            Some(floor) => display.max(floor),
+            None => display,
+        };
+
+        match self.rating_ceiling {
+            Some(ceiling) => display.min(ceiling),
+            None => display,
+        }
+    }
+
+    /// Internal size of a difference of display ratings, unlike [`Config::rating_from_display`] the base rating doesn't matter
+    pub fn rating_delta_from_display(&self, delta: f64) -> f64 {
+        delta / self.spread
+    }
+
+    /// Fails unless `max` is a positive finite limit of `max_rating_change_per_play`
+    pub fn check_max_rating_change(max: f64) -> Result<(), String> {
+        if max > 0.0 && max.is_finite() {
+            Ok(())
+        } else {
+            Err(format!(
+                "max_rating_change_per_play is {max}, it must be a positive number"
+            ))
+        }
+    }
+
+    pub fn α_from_display(&self, display: f64) -> f64 {
+        display / self.spread
+    }
+
+    pub fn α_to_display(&self, α: f64) -> f64 {
+        α * self.spread
+    }
+
+    /// Fails if `format` isn't a valid chrono format string for dates
+    pub fn check_date_format(format: &str) -> Result<(), String> {
+        use chrono::format::{Item, StrftimeItems};
+
+        if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+            return Err(format!("'{format}' is not a valid date format"));
+        }
+
+        // Valid items may still need a time, which a date doesn't have
+        let mut formatted = String::new();
+        let date = chrono::NaiveDate::default();
+        fmt::Write::write_fmt(&mut formatted, format_args!("{}", date.format(format)))
+            .map_err(|_| format!("'{format}' can't format dates, only times"))?;
+
+        Ok(())
+    }
+
+    /// Whether `answer` to a confirmation prompt is one of the `confirm_words`
+    pub fn is_confirmation(&self, answer: &str) -> bool {
+        let answer = answer.trim().to_lowercase();
+
+        self.confirm_words
+            .iter()
+            .any(|word| word.trim().to_lowercase() == answer)
+    }
+
+    /// The answers to show after a confirmation prompt, e.g. "(y/N)", or "(igen/N)" with `confirm_words` "igen" and "i"
+    pub fn confirmation_hint(&self) -> String {
+        match self.confirm_words.first() {
+            Some(word) => format!("({}/N)", word.trim()),
+            None => "(N)".to_owned(),
+        }
+    }
+
+    /// Formats `date` with the configured `date_format`, or as YYYY-MM-DD if it fails [`Config::check_date_format`]
+    pub fn format_date(&self, date: chrono::NaiveDate) -> String {
+        let mut formatted = String::new();
+
+        match fmt::Write::write_fmt(
+            &mut formatted,
+            format_args!("{}", date.format(&self.date_format)),
+        ) {
+            Ok(()) => formatted,
+            Err(_) => date.to_string(),
+        }
+    }
+
+    /// Like [`Config::format_date`], followed by the session if there is one, e.g. "2024-06-01 #2"
+    pub fn format_time_point(&self, point: TimePoint) -> String {
+        match point.session {
+            Some(session) => format!("{} #{session}", self.format_date(point.date)),
+            None => self.format_date(point.date),
+        }
+    }
+
+    pub fn check_game_count(&self, game_count: usize) -> Result<(), String> {
+        if game_count == 0 {
+            Err("game count must be positive".to_owned())
+        } else if game_count > self.max_game_count {
+            Err(format!(
+                "game count {game_count} is larger than the maximum of {}, see config max-game-count",
+                self.max_game_count
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The checks a play has to pass before it's recorded: its game count, possibly fractional, and its scores summing to zero
+    pub fn check_play(&self, play: &Play) -> Result<(), String> {
+        self.check_game_count(play.game_count)?;
+        if let Some(games) = play.fractional_games {
+            if !games.is_finite() || games <= 0.0 {
+                return Err(format!(
+                    "game count must be a positive number, found {games}"
+                ));
+            }
+        }
+
+        let sum = play.outcomes.iter().map(|o| o.score).sum::<i64>();
+        if sum != 0 {
+            return Err(format!("scores of the play sum to {sum}"));
+        }
+
+        Ok(())
+    }
+
+    /// The α affecting `player`, given the global α
+    pub fn player_α(&self, player: &str, α: f64) -> f64 {
+        α * self.player_alpha.get(player).copied().unwrap_or(1.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[non_exhaustive]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    AddPlayer(AddPlayer),
+    Play(Play),
+    AdjustAlpha(AdjustAlpha),
+    SetRating(SetRating),
+    SeasonBoundary(SeasonBoundary),
+}
+
+impl Change {
+    /// Name of the variant, as it appears in the data file
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            Change::AddPlayer(_) => "add_player",
+            Change::Play(_) => "play",
+            Change::AdjustAlpha(_) => "adjust_alpha",
+            Change::SeasonBoundary(_) => "season_boundary",
+            Change::SetRating(_) => "set_rating",
+        }
+    }
+
+    /// The change as an English sentence, e.g. for players who don't read the data file
+    ///
+    /// Ratings and the score multiplier are in display units of `config`.
+    pub fn narrative(&self, config: &Config) -> String {
+        let sentence = match self {
+            Change::AddPlayer(addition) => format!(
+                "{} joined with a rating of {:.1}",
+                addition.name,
+                config.rating_to_display(addition.rating)
+            ),
+            Change::Play(play) => {
+                let [a, b, c] = play.outcomes.each_ref().map(|o| &o.player);
+                let [x, y, z] = play.outcomes.each_ref().map(|o| o.score);
+                let games = if play.games() == 1.0 { "game" } else { "games" };
+                let mut sentence = format!(
+                    "{a}, {b}, and {c} played {} {games}; scores {x}, {y}, {z}",
+                    play.games()
+                );
+                if let Some(note) = &play.note {
+                    sentence += &format!(" ({note})");
+                }
+                sentence
+            }
+            Change::AdjustAlpha(adjust) => {
+                let mut sentence = format!(
+                    "the score multiplier was changed to {}",
+                    config.α_to_display(adjust.value)
+                );
+                if let Some(reason) = &adjust.reason {
+                    sentence += &format!(" because {reason}");
+                }
+                sentence
+            }
+            Change::SetRating(set) => format!(
+                "{}'s rating was set to {:.1}",
+                set.player,
+                config.rating_to_display(set.rating)
+            ),
+            Change::SeasonBoundary(boundary) => format!("season {} started", boundary.name),
+        };
+
+        // Names are kept as they are, only sentences starting with our own words are capitalized
+        let starts_with_name = matches!(
+            self,
+            Change::AddPlayer(_) | Change::Play(_) | Change::SetRating(_)
+        );
+
+        match self.date() {
+            Some(date) => format!("On {}, {sentence}.", config.format_date(date)),
+            None if starts_with_name => format!("{sentence}."),
+            None => {
+                let mut chars = sentence.chars();
+                let first = chars.next().map(|c| c.to_uppercase().to_string());
+                format!("{}{}.", first.unwrap_or_default(), chars.as_str())
+            }
+        }
+    }
+
+    /// Fails for variants without a date
+    pub fn set_date(&mut self, date: chrono::NaiveDate) -> Result<(), Box<dyn Error>> {
+        match self {
+            Change::Play(play) => {
+                play.date = date;
+                Ok(())
+            }
+            Change::SeasonBoundary(boundary) => {
+                boundary.date = Some(date);
+                Ok(())
+            }
+            Change::AdjustAlpha(adjust) => {
+                adjust.date = Some(date);
+                Ok(())
+            }
+            Change::AddPlayer(_) | Change::SetRating(_) => {
+                Err(format!("{} changes don't have a date", self.variant_name()).into())
+            }
+        }
+    }
+
+    /// Equality, except that floats only need to be within `eps` of each other
+    pub fn approx_eq(&self, other: &Change, eps: f64) -> bool {
+        let close = |a: f64, b: f64| (a - b).abs() <= eps;
+
+        match (self, other) {
+            (Change::AddPlayer(a), Change::AddPlayer(b)) => {
+                a.name == b.name && close(a.rating, b.rating) && a.games_played == b.games_played
+            }
+            (Change::SetRating(a), Change::SetRating(b)) => {
+                a.player == b.player && close(a.rating, b.rating)
+            }
+            (Change::AdjustAlpha(a), Change::AdjustAlpha(b)) => {
+                close(a.value, b.value) && a.date == b.date && a.reason == b.reason
+            }
+            (Change::Play(a), Change::Play(b)) => {
+                a.game_count == b.game_count
+                    && a.date == b.date
+                    && a.outcomes == b.outcomes
+                    && a.note == b.note
+                    && a.session == b.session
+                    && match (a.fractional_games, b.fractional_games) {
+                        (Some(a), Some(b)) => close(a, b),
+                        (a, b) => a == b,
+                    }
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Whether the change mentions `player`
+    pub fn references(&self, player: &str) -> bool {
+        match self {
+            Change::AddPlayer(addition) => addition.name == player,
+            Change::Play(play) => play.outcomes.iter().any(|outcome| outcome.player == player),
+            Change::SetRating(set) => set.player == player,
+            Change::AdjustAlpha(_) | Change::SeasonBoundary(_) => false,
+        }
+    }
+
+    /// The date of the change, if it has one
+    pub fn date(&self) -> Option<chrono::NaiveDate> {
+        match self {
+            Change::Play(play) => Some(play.date),
+            Change::SeasonBoundary(boundary) => boundary.date,
+            Change::AdjustAlpha(adjust) => adjust.date,
+            Change::AddPlayer(_) | Change::SetRating(_) => None,
+        }
+    }
+
+    /// The date and, for plays, the session, see [`Data::rating_timeline`]
+    pub fn time_point(&self) -> Option<TimePoint> {
+        let session = match self {
+            Change::Play(play) => play.session,
+            _ => None,
+        };
+
+        self.date().map(|date| TimePoint { date, session })
+    }
+}
+
+/// A date and optionally a session on that date, see [`Play::session`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimePoint {
+    pub date: chrono::NaiveDate,
+    pub session: Option<u32>,
+}
+
+impl fmt::Display for TimePoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.session {
+            Some(session) => write!(f, "{} #{session}", self.date),
+            None => write!(f, "{}", self.date),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
+pub struct AddPlayer {
+    pub name: String,
+    pub rating: f64,
+    /// Games played in compacted history, counted towards the ranking minimum, see [`Data::compact_before`]
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub games_played: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Time spent by [`Data::evaluate_timed`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalTimings {
+    /// Number of changes applied
+    pub changes: usize,
+    /// Rating updates of plays
+    pub plays: Duration,
+    /// Every other change, e.g. adding players
+    pub other_changes: Duration,
+    pub centering: Duration,
+    /// Including the bookkeeping of the measurement itself
+    pub total: Duration,
+}
+
+impl fmt::Display for EvalTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "changes: {}", self.changes)?;
+        writeln!(f, "plays: {:?}", self.plays)?;
+        writeln!(f, "other changes: {:?}", self.other_changes)?;
+        writeln!(f, "centering: {:?}", self.centering)?;
+        write!(f, "total: {:?}", self.total)
+    }
+}
+
+/// Sets α for the following plays
+///
+/// Older files store only the value, as a bare number.
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[serde(from = "AdjustAlphaRepr")]
+pub struct AdjustAlpha {
+    pub value: f64,
+    #[serde(
+        default,
+        with = "date::optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub date: Option<chrono::NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AdjustAlphaRepr {
+    Bare(f64),
+    Full {
+        value: f64,
+        #[serde(default, with = "date::optional")]
+        date: Option<chrono::NaiveDate>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+impl From<AdjustAlphaRepr> for AdjustAlpha {
+    fn from(repr: AdjustAlphaRepr) -> Self {
+        match repr {
+            AdjustAlphaRepr::Bare(value) => AdjustAlpha {
+                value,
+                ..Default::default()
+            },
+            AdjustAlphaRepr::Full {
+                value,
+                date,
+                reason,
+            } => AdjustAlpha {
+                value,
+                date,
+                reason,
+            },
+        }
+    }
+}
+
+/// Marks the start of a season, doesn't affect ratings
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
+pub struct SeasonBoundary {
+    pub name: String,
+    #[serde(
+        default,
+        with = "date::optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub date: Option<chrono::NaiveDate>,
+}
+
+/// Overrides the rating of an existing player
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
+pub struct SetRating {
+    pub player: String,
+    pub rating: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
+pub struct Play {
+    pub game_count: usize,
+    #[serde(with = "date")]
+    pub date: chrono::NaiveDate,
+    pub outcomes: [Outcome; 3],
+    /// Free text, doesn't affect ratings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// The exact number of games of an interrupted session, e.g. 2.5, used by the math instead of `game_count`
+    ///
+    /// `game_count` is then this rounded up, and is still what counts towards the games played.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fractional_games: Option<f64>,
+    /// Tells apart separate sessions on the same date, e.g. 1 and 2 for an afternoon and an evening
+    ///
+    /// Plays of the same date and session are one point of [`Data::rating_timeline`], so by default a date is one point.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u32>,
+}
+
+impl Play {
+    /// Sorts the outcomes by player name, so the same table is always stored the same way.
+    ///
+    /// The position of an outcome doesn't affect the math, the three players are treated symmetrically.
+    pub fn canonicalize(&mut self) {
+        self.outcomes
+            .sort_unstable_by(|outcome_a, outcome_b| outcome_a.player.cmp(&outcome_b.player));
+    }
+
+    /// Number of games the math uses, fractional for interrupted sessions
+    pub fn games(&self) -> f64 {
+        self.fractional_games.unwrap_or(self.game_count as f64)
+    }
+
+    /// Sets a possibly fractional number of games, see [`Play::fractional_games`]
+    pub fn set_games(&mut self, games: f64) -> Result<(), String> {
+        if !games.is_finite() || games <= 0.0 {
+            return Err(format!(
+                "game count must be a positive number, found {games}"
+            ));
+        }
+
+        self.game_count = games.ceil() as usize;
+        self.fractional_games = (games.fract() != 0.0).then_some(games);
+        Ok(())
+    }
+
+    pub fn now(game_count: usize, outcomes: [Outcome; 3]) -> Self {
+        Play {
+            game_count,
+            date: chrono::Local::now().date_naive(),
+            outcomes,
+            note: None,
+            fractional_games: None,
+            session: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Deserialize, Serialize)]
+pub struct Outcome {
+    pub player: String,
+    pub score: i64,
+}
+
+/// Results of two players against each other, see [`Data::head_to_head`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeadToHead {
+    /// Plays both players were part of
+    pub plays: usize,
+    pub games: usize,
+    /// Total scores of the two players in these plays
+    pub scores: [i64; 2],
+}
+
+impl HeadToHead {
+    /// How much more the first player scored than the second one
+    pub fn balance(&self) -> i64 {
+        self.scores[0] - self.scores[1]
+    }
+}
+
+/// A change affecting a single player, see [`Data::rating_events`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingEvent<'d> {
+    pub history_index: usize,
+    pub change: &'d Change,
+    /// `None` if the player didn't exist before the change
+    pub before: Option<f64>,
+    pub after: f64,
+}
+
+impl RatingEvent<'_> {
+    /// `None` if the player didn't exist before the change
+    pub fn delta(&self) -> Option<f64> {
+        self.before.map(|before| self.after - before)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Evaluation {
+    pub α: f64,
+    pub ratings: HashMap<String, f64>,
+    /// Started games, an interrupted session of 2.5 games counts as 3, see [`Play::fractional_games`]
+    pub games_played: HashMap<String, usize>,
+}
+
+impl Evaluation {
+    pub fn new(config: &Config) -> Self {
+        Evaluation {
+            α: config.starting_alpha,
+            ratings: HashMap::new(),
+            games_played: HashMap::new(),
+        }
+    }
+
+    /// Applies a single change of the history
+    pub fn change(&mut self, change: &Change, config: &Config) {
+        self.apply(change, config);
+
+        if config.clamp_internal_ratings {
+            let players: Vec<&str> = match change {
+                Change::AddPlayer(addition) => vec![&addition.name],
+                Change::Play(play) => play.outcomes.iter().map(|o| o.player.as_str()).collect(),
+                Change::SetRating(set) => vec![&set.player],
+                Change::AdjustAlpha(_) | Change::SeasonBoundary(_) => Vec::new(),
+            };
+
+            self.clamp_internal(players, config);
+        }
+    }
+
+    /// Applies plays with pairwise disjoint players, computing their rating changes concurrently
+    ///
+    /// Same as applying them one by one, as a play only reads and writes the ratings of its players.
+    #[cfg(feature = "parallel")]
+    fn apply_disjoint_plays(&mut self, plays: &[&Play], config: &Config) {
+        use rayon::prelude::*;
+
+        let new_ratings: Vec<[f64; 3]> = plays
+            .par_iter()
+            .map(|play| self.play_ratings(play, config))
+            .collect();
+
+        for (play, new_ratings) in plays.iter().zip(new_ratings) {
+            self.set_play_ratings(play, new_ratings);
+
+            if config.clamp_internal_ratings {
+                self.clamp_internal(play.outcomes.iter().map(|o| o.player.as_str()), config);
+            }
+        }
+    }
+
+    fn clamp_internal<'p>(&mut self, players: impl IntoIterator<Item = &'p str>, config: &Config) {
+        for player in players {
+            let rating = self.ratings.get_mut(player).unwrap();
+            *rating = config.rating_from_display(
+                config.clamp_display(*rating * config.spread + config.base_rating),
+            );
+        }
+    }
+
+    fn apply(&mut self, change: &Change, config: &Config) {
+        match change {
+            Change::AddPlayer(addition) => {
+                self.ratings.insert(addition.name.clone(), addition.rating);
+                *self.games_played.entry(addition.name.clone()).or_insert(0) +=
+                    addition.games_played;
+            }
+            Change::Play(play) => {
+                let new_ratings = self.play_ratings(play, config);
+                self.set_play_ratings(play, new_ratings);
+            }
+            Change::SetRating(set) => {
+                *self.ratings.get_mut(&set.player).unwrap() = set.rating;
+            }
+            Change::AdjustAlpha(adjust) => self.α = adjust.value,
+            Change::SeasonBoundary(_) => {}
+        }
+    }
+
+    /// Ratings of the players after `play`, limited by `max_rating_change_per_play`
+    fn play_ratings(&self, play: &Play, config: &Config) -> [f64; 3] {
+        let new_ratings = self.unclamped_ratings(play, config);
+
+        [0, 1, 2].map(|i| {
+            let rating = self.ratings[&play.outcomes[i].player];
+            match config.max_rating_change_per_play {
+                Some(max) => {
+                    let max = config.rating_delta_from_display(max);
+                    new_ratings[i].clamp(rating - max, rating + max)
+                }
+                None => new_ratings[i],
+            }
+        })
+    }
+
+    fn set_play_ratings(&mut self, play: &Play, new_ratings: [f64; 3]) {
+        for (outcome, new_rating) in play.outcomes.iter().zip(new_ratings) {
+            *self.ratings.get_mut(&outcome.player).unwrap() = new_rating;
+            *self.games_played.get_mut(&outcome.player).unwrap() += play.game_count;
+        }
+    }
+
+    /// Ratings of the players after `play`, ignoring `max_rating_change_per_play`
+    fn unclamped_ratings(&self, play: &Play, config: &Config) -> [f64; 3] {
+        let selected_ratings = play
+            .outcomes
+            .clone()
+            .map(|outcome| self.ratings[&outcome.player]);
+        let alphas = play
+            .outcomes
+            .clone()
+            .map(|outcome| config.player_α(&outcome.player, self.α));
+        let scores = play.outcomes.clone().map(|outcome| outcome.score);
+
+        rating_change_fractional(alphas, play.games(), selected_ratings, scores)
+    }
+
+    /// Players whose rating change would be clamped by `max_rating_change_per_play` if `play` was applied next
+    pub fn clamped_players<'p>(&self, play: &'p Play, config: &Config) -> Vec<&'p str> {
+        let Some(max) = config.max_rating_change_per_play else {
+            return Vec::new();
+        };
+        let max = config.rating_delta_from_display(max);
+
+        play.outcomes
+            .iter()
+            .zip(self.unclamped_ratings(play, config))
+            .filter(|(outcome, new_rating)| {
+                (new_rating - self.ratings[&outcome.player]).abs() > max
+            })
+            .map(|(outcome, _)| outcome.player.as_str())
+            .collect()
+    }
+
+    /// Expected total scores of the players over `games` games, which may be fractional, see [`Play::games`]
+    ///
+    /// Ratings are at equilibrium if each player scores their rating difference from the table average per game.
+    pub fn predict(&self, players: [&str; 3], games: f64) -> [f64; 3] {
+        let ratings = players.map(|player| self.ratings[player]);
+        let average = ratings.iter().sum::<f64>() / 3.0;
+
+        ratings.map(|rating| (rating - average) * games)
+    }
+
+    /// Probabilities of each player finishing with the highest score
+    ///
+    /// The rating model only predicts scores, so this assumes a multinomial logistic model on top of it: the odds of two players winning are `e` to the power of their internal rating difference, i.e. their display rating difference divided by the spread. Ties are not modelled.
+    pub fn win_probabilities(&self, players: [&str; 3]) -> [f64; 3] {
+        let ratings = players.map(|player| self.ratings[player]);
+        let max = ratings.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let weights = ratings.map(|rating| (rating - max).exp());
+        let total = weights.iter().sum::<f64>();
+
+        weights.map(|weight| weight / total)
+    }
+
+    /// Average absolute difference of the actual and the predicted scores per player per game
+    pub fn surprise(&self, outcomes: &[Outcome; 3], games: f64) -> f64 {
+        let predicted = self.predict([0, 1, 2].map(|i| outcomes[i].player.as_str()), games);
+
+        outcomes
+            .iter()
+            .zip(predicted)
+            .map(|(outcome, predicted)| (outcome.score as f64 - predicted).abs())
+            .sum::<f64>()
+            / 3.0
+            / games
+    }
+
+    /// 1-based position of `player` in the [`Evaluation::leaderboard`] and the number of players in it, `None` if they aren't on it
+    ///
+    /// Tied players share the best rank of the tie.
+    pub fn rank(&self, player: &str, min_games: usize) -> Option<(usize, usize)> {
+        let leaderboard = self.leaderboard(min_games);
+        let (_, rating) = leaderboard.iter().find(|(other, _)| *other == player)?;
+        let higher = leaderboard
+            .iter()
+            .filter(|(_, other)| other > rating)
+            .count();
+
+        Some((higher + 1, leaderboard.len()))
+    }
+
+    /// See [`Evaluation::rating_sum`]
+    pub fn average_rating(&self) -> f64 {
+        self.rating_sum() / self.ratings.len() as f64
+    }
+
+    /// Summed in the order of the names, so that the result doesn't depend on the order of the map
+    pub fn rating_sum(&self) -> f64 {
+        let mut ratings: Vec<(&String, &f64)> = self.ratings.iter().collect();
+        ratings.sort_unstable_by_key(|(player, _)| *player);
+
+        ratings.into_iter().map(|(_, rating)| rating).sum()
+    }
+
+    /// Shifts all ratings so that their average is 0
+    pub fn center(&mut self) {
+        if self.ratings.is_empty() {
+            return;
+        }
+
+        let average = self.average_rating();
+
+        for rating in self.ratings.values_mut() {
+            *rating -= average;
+        }
+    }
+
+    /// The three of `candidates` with the smallest difference between their highest and lowest rating
+    ///
+    /// Such a triple is always adjacent in rating order, so no combinatorial search is needed. `None` if there are fewer than three candidates.
+    pub fn most_balanced<'c>(&self, candidates: &[&'c str]) -> Option<[&'c str; 3]> {
+        let mut sorted: Vec<(&str, f64)> = candidates
+            .iter()
+            .map(|player| (*player, self.ratings[*player]))
+            .collect();
+        sorted.sort_unstable_by(|(player_a, a), (player_b, b)| {
+            a.total_cmp(b).then(player_a.cmp(player_b))
+        });
+
+        sorted
+            .windows(3)
+            .min_by(|a, b| (a[2].1 - a[0].1).total_cmp(&(b[2].1 - b[0].1)))
+            .map(|triple| [triple[0].0, triple[1].0, triple[2].0])
+    }
+
+    /// Shifts all ratings so that the rating of `anchor` is 0, does nothing if there is no such player
+    pub fn anchor_at(&mut self, anchor: &str) {
+        let Some(&offset) = self.ratings.get(anchor) else {
+            return;
+        };
+
+        for rating in self.ratings.values_mut() {
+            *rating -= offset;
+        }
+    }
+
+    /// Players with at least `min_games` games, sorted by descending rating
+    pub fn leaderboard(&self, min_games: usize) -> Vec<(&str, f64)> {
+        let mut ratings: Vec<(&str, f64)> = self
+            .ratings
+            .iter()
+            .filter(|(player, _)| self.games_played[*player] >= min_games)
+            .map(|(player, rating)| (player.as_str(), *rating))
+            .collect();
+
+        ratings.sort_unstable_by(|(_player_a, rating_a), (_player_b, rating_b)| {
+            rating_a.partial_cmp(rating_b).unwrap().reverse()
+        });
+
+        ratings
+    }
+
+    /// The spread and base rating which display the ratings with the given mean and population standard deviation
+    ///
+    /// Rating floor and ceiling are ignored. Fails if the ratings don't vary, e.g. with fewer than two players.
+    pub fn fit_display(&self, target_mean: f64, target_std: f64) -> Result<(f64, f64), String> {
+        if !target_mean.is_finite() {
+            return Err(format!(
+                "the target mean is {target_mean}, it must be a finite number"
+            ));
+        }
+        if !(target_std > 0.0 && target_std.is_finite()) {
+            return Err(format!(
+                "the target standard deviation is {target_std}, it must be positive"
+            ));
+        }
+
+        let mean = self.average_rating();
+        let variance = self
+            .ratings
+            .values()
+            .map(|rating| (rating - mean).powi(2))
+            .sum::<f64>()
+            / self.ratings.len() as f64;
+        if variance.is_nan() || variance <= 0.0 {
+            return Err("the ratings don't vary, so any spread displays them the same".to_owned());
+        }
+
+        let spread = target_std / variance.sqrt();
+        Ok((spread, target_mean - mean * spread))
+    }
+
+    /// Number of players per display rating bin, bin `i` holds ratings in `[i * bin_width, (i + 1) * bin_width)`
+    ///
+    /// Only non-empty bins are returned.
+    pub fn histogram(&self, bin_width: f64, config: &Config) -> BTreeMap<i64, usize> {
+        let mut bins = BTreeMap::new();
+
+        for rating in self.ratings.values() {
+            let bin = (config.rating_to_display(*rating) / bin_width).floor() as i64;
+            *bins.entry(bin).or_insert(0) += 1;
+        }
+
+        bins
+    }
+
+    /// Whether α and the rating of every player are within `eps` of `other`'s, and both have the same players
+    ///
+    /// Game counts aren't compared. Use this rather than `==` for evaluations computed in different ways, as float results may differ in the last bits.
+    pub fn approx_eq(&self, other: &Evaluation, eps: f64) -> bool {
+        (self.α - other.α).abs() <= eps
+            && self.ratings.len() == other.ratings.len()
+            && self.ratings.iter().all(|(player, rating)| {
+                other
+                    .ratings
+                    .get(player)
+                    .is_some_and(|other| (rating - other).abs() <= eps)
+            })
+    }
+
+    /// Internal rating differences (`self - other`) of players present in both evaluations
+    ///
+    /// Players present in only one of them are left out, see [`Evaluation::players_missing_from`].
+    pub fn diff(&self, other: &Evaluation) -> HashMap<String, f64> {
+        self.ratings
+            .iter()
+            .filter_map(|(player, rating)| {
+                other
+                    .ratings
+                    .get(player)
+                    .map(|other_rating| (player.clone(), rating - other_rating))
+            })
+            .collect()
+    }
+
+    /// Players of `self` which are not in `other`, sorted by name
+    pub fn players_missing_from(&self, other: &Evaluation) -> Vec<&str> {
+        let mut players: Vec<&str> = self
+            .ratings
+            .keys()
+            .filter(|player| !other.ratings.contains_key(*player))
+            .map(|player| player.as_str())
+            .collect();
+
+        players.sort_unstable();
+        players
+    }
+
+    pub fn matching_names<'s>(&'s self, pattern: &'s str) -> Vec<&'s str> {
+        self.matching_names_with(pattern, MatchMode::default())
+    }
+
+    /// Blank patterns match nothing
+    pub fn matching_names_with<'s>(&'s self, pattern: &'s str, mode: MatchMode) -> Vec<&'s str> {
+        if pattern.trim().is_empty() {
+            return Vec::new();
+        }
+
+        if mode == MatchMode::StrictExact && self.ratings.keys().any(|name| name == pattern) {
+            return vec![pattern];
+        }
+
+        let mut matches: Vec<&str> = self
+            .ratings
+            .keys()
+            .filter(|name| *name == pattern || match_names(name, pattern))
+            .map(|name| name.as_str())
+            .collect();
+
+        matches.sort_unstable();
+        matches
+    }
+}
+
+/// Decides what happens if a pattern equals a name, but also matches other names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Only the exact match is returned, e.g. "Ann" matches only "Ann" even if "Anna" exists
+    #[default]
+    StrictExact,
+    /// Every match is returned, e.g. "Ann" matches both "Ann" and "Anna"
+    PreferPrefix,
+}
+
+fn match_names(matched: &str, pattern: &str) -> bool {
+    let mut split_name = matched.split(' ').filter(|x| !x.is_empty());
+    let split_pattern = pattern.split(' ').filter(|x| !x.is_empty());
+
+    for pattern_word in split_pattern {
+        loop {
+            match split_name.next() {
+                Some(word) if word.starts_with(pattern_word) => break,
+                Some(_word) => {}
+                None => return false,
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn play(date: chrono::NaiveDate, outcomes: [(&str, i64); 3]) -> Play {
+        Play {
+            game_count: 1,
+            date,
+            outcomes: outcomes.map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn name_matching() {
+        assert!(match_names("Németh Marcell", "Németh M"));
+        assert!(match_names("Németh Márton", "Németh M"));
+        assert!(!match_names("Németh Dominik", "Németh M"));
+        assert!(match_names("Németh Marcell", "Ma"));
+        assert!(!match_names("Németh Márton", "Ma"));
+    }
+
+    #[test]
+    fn history_json_round_trip() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 0.0);
+        data.add_player("C".to_owned(), 0.0);
+        data.play(Play {
+            game_count: 2,
+            date: chrono::NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+            outcomes: [
+                Outcome {
+                    player: "A".to_owned(),
+                    score: 4,
+                },
+                Outcome {
+                    player: "B".to_owned(),
+                    score: -2,
+                },
+                Outcome {
+                    player: "C".to_owned(),
+                    score: -2,
+                },
+            ],
+            note: None,
+            fractional_games: None,
+            session: None,
+        });
+        data.adjust_α(0.05);
+
+        let json = to_json(&data.history).unwrap();
+
+        assert!(json.contains("\"add_player\""));
+        assert!(json.contains("\"adjust_alpha\""));
+        assert!(json.contains("\"date\": \"2024-06-01\""));
+
+        let history: Vec<Change> = from_json(&json).unwrap();
+        assert_eq!(history, data.history);
+    }
+
+    #[test]
+    fn import_appended_history() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 0.0);
+
+        let valid = r#"[
+            { "add_player": { "name": "C", "rating": 0.0 } },
+            { "play": {
+                "game_count": 1,
+                "date": "2024-06-01",
+                "outcomes": [
+                    { "player": "A", "score": 2 },
+                    { "player": "B", "score": -1 },
+                    { "player": "C", "score": -1 }
+                ]
+            } }
+        ]"#;
+        let invalid = valid.replace("\"player\": \"C\"", "\"player\": \"D\"");
+
+        let appended = Data {
+            history: [data.history.clone(), from_json(valid).unwrap()].concat(),
+            ..Default::default()
+        };
+        assert_eq!(appended.check(), vec![]);
+
+        let appended = Data {
+            history: [data.history, from_json(&invalid).unwrap()].concat(),
+            ..Default::default()
+        };
+        let problems = appended.check();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::UnknownPlayer);
+        assert_eq!(problems[0].history_index, Some(3));
+    }
+
+    #[test]
+    fn average_new_player_policy() {
+        let mut data = Data::default();
+        data.config.new_player_policy = NewPlayerPolicy::Average;
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 4.0);
+
+        assert_eq!(data.new_player_rating(), 2.0);
+    }
+
+    #[test]
+    fn activity_date_counts() {
+        let date = |day| chrono::NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.play(Play {
+            date: date(2),
+            ..Default::default()
+        });
+        data.play(Play {
+            date: date(1),
+            ..Default::default()
+        });
+        data.play(Play {
+            date: date(2),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            data.activity_dates().into_iter().collect::<Vec<_>>(),
+            vec![(date(1), 1), (date(2), 2)]
+        );
+    }
+
+    #[test]
+    fn player_alpha_multiplier() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 0.0);
+        data.add_player("C".to_owned(), 0.0);
+        data.play(Play {
+            game_count: 3,
+            outcomes: [
+                Outcome {
+                    player: "A".to_owned(),
+                    score: 6,
+                },
+                Outcome {
+                    player: "B".to_owned(),
+                    score: -3,
+                },
+                Outcome {
+                    player: "C".to_owned(),
+                    score: -3,
+                },
+            ],
+            ..Default::default()
+        });
+
+        let normal = data.evaluate().ratings["A"];
+        data.config.player_alpha.insert("A".to_owned(), 2.0);
+        let doubled = data.evaluate().ratings["A"];
+
+        assert!((doubled / normal - 2.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn set_rating_mid_history() {
+        let win = play(Default::default(), [("A", 2), ("B", -1), ("C", -1)]);
+
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 0.0);
+        data.add_player("C".to_owned(), 0.0);
+        data.play(win.clone());
+        data.set_rating("A".to_owned(), 0.0);
+
+        assert_eq!(data.evaluate().ratings["A"], 0.0);
+        assert_eq!(data.check(), vec![]);
+
+        let b = data.evaluate().ratings["B"];
+        data.play(win);
+
+        let expected = rating_change(data.config.starting_alpha, 1, [0.0, b, b], [2, -1, -1]);
+        assert!((data.evaluate().ratings["A"] - expected[0]).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rating_conservation() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 1.0);
+        data.add_player("B".to_owned(), 0.0);
+        data.add_player("C".to_owned(), -0.5);
+        data.play(play(Default::default(), [("A", 4), ("B", -2), ("C", -2)]));
+
+        assert_eq!(data.check_conservation(1e-9), vec![]);
+
+        data.play(play(Default::default(), [("A", 4), ("B", 2), ("C", -2)]));
+        let problems = data.check_conservation(1e-9);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::NotConserved);
+        assert_eq!(problems[0].history_index, Some(4));
+    }
+
+    #[test]
+    fn data_file_formats() {
+        let mut data = Data::default();
+        data.config.spread = 75.0;
+        data.add_player("A".to_owned(), 0.5);
+        data.play(Play {
+            game_count: 1,
+            date: chrono::NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+            ..Default::default()
+        });
+
+        for extension in ["json", "toml"] {
+            let path = env::temp_dir().join(format!(
+                "ultira-format-test-{}.{extension}",
+                std::process::id()
+            ));
+
+            write_data(&path, &data).unwrap();
+            assert_eq!(read_data(&path).unwrap(), data);
+
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn dot_export() {
+        let mut data = Data::default();
+        for name in ["A", "B", "C", "D"] {
+            data.add_player(name.to_owned(), 0.0);
+        }
+        for (game_count, players) in [(2, ["B", "A", "C"]), (3, ["A", "B", "D"])] {
+            data.play(Play {
+                game_count,
+                outcomes: players.map(|player| Outcome {
+                    player: player.to_owned(),
+                    score: 0,
+                }),
+                ..Default::default()
+            });
+        }
+
+        let dot = data.to_dot();
+
+        assert!(dot.starts_with("graph ultira {"));
+        assert!(dot.contains("    \"D\";\n"));
+        assert!(dot.contains("\"A\" -- \"B\" [label = \"5\", weight = 5];"));
+        assert!(dot.contains("\"A\" -- \"C\" [label = \"2\", weight = 2];"));
+        assert!(dot.contains("\"B\" -- \"D\" [label = \"3\", weight = 3];"));
+        assert!(!dot.contains("\"C\" -- \"D\""));
+    }
+
+    #[test]
+    fn internal_score_multiplier() {
+        let mut data = Data::default();
+        data.adjust_score_multiplier(3.0);
+
+        let eval = data.evaluate();
+        let display = data.config.α_to_display(eval.α);
+
+        assert!((data.config.α_from_display(display) - eval.α).abs() < 1e-15);
+        assert!((eval.α - 3.0 / data.config.spread).abs() < 1e-15);
+    }
+
+    #[test]
+    fn duplicate_add_player() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 0.0);
+        data.add_player("A".to_owned(), 1.0);
+
+        let problems = data.check_duplicate_players();
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::DuplicateAddPlayer);
+        assert_eq!(problems[0].history_index, Some(2));
+        assert!(problems[0].message.contains("'A'"));
+        assert!(problems[0].message.contains("0, 2"));
+    }
+
+    #[test]
+    fn leaderboard_min_games() {
+        let mut data = Data::default();
+        for name in ["A", "B", "C", "D"] {
+            data.add_player(name.to_owned(), 0.0);
+        }
+        data.play(Play {
+            game_count: 2,
+            outcomes: ["A", "B", "C"].map(|player| Outcome {
+                player: player.to_owned(),
+                score: 0,
+            }),
+            ..Default::default()
+        });
+        data.set_rating("D".to_owned(), 1.0);
+
+        let eval = data.evaluate();
+
+        assert_eq!(eval.games_played["A"], 2);
+        assert_eq!(eval.games_played["D"], 0);
+        assert_eq!(eval.leaderboard(0)[0], ("D", 1.0));
+        assert!(eval.leaderboard(1).iter().all(|(player, _)| *player != "D"));
+        assert_eq!(eval.leaderboard(1).len(), 3);
+        assert_eq!(eval.ratings["D"], 1.0);
+    }
+
+    #[test]
+    fn evaluation_diff() {
+        let eval = |ratings: [(&str, f64); 2]| Evaluation {
+            ratings: ratings
+                .map(|(player, rating)| (player.to_owned(), rating))
+                .into(),
+            ..Default::default()
+        };
+
+        let this = eval([("A", 1.0), ("B", 0.5)]);
+        let other = eval([("A", 0.25), ("C", 0.0)]);
+
+        assert_eq!(this.diff(&other), HashMap::from([("A".to_owned(), 0.75)]));
+        assert_eq!(other.diff(&this), HashMap::from([("A".to_owned(), -0.75)]));
+        assert_eq!(this.players_missing_from(&other), vec!["B"]);
+        assert_eq!(other.players_missing_from(&this), vec!["C"]);
+    }
+
+    #[test]
+    fn draw_between_equals() {
+        let mut data = Data::default();
+        for name in ["A", "B", "C"] {
+            data.add_player(name.to_owned(), 0.5);
+        }
+        data.play(Play {
+            game_count: 3,
+            outcomes: ["A", "B", "C"].map(|player| Outcome {
+                player: player.to_owned(),
+                score: 0,
+            }),
+            ..Default::default()
+        });
+
+        let eval = data.evaluate();
+
+        for player in ["A", "B", "C"] {
+            assert!((eval.ratings[player] - 0.5).abs() < 1e-12);
+            assert_eq!(eval.games_played[player], 3);
+        }
+    }
+
+    #[test]
+    fn audit_log_lines() {
+        let path = env::temp_dir().join(format!("ultira-audit-test-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        append_audit(&path, "Play(Play { game_count: 1 })").unwrap();
+        let log = fs::read_to_string(&path).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.ends_with(": Play(Play { game_count: 1 })\n"));
+
+        append_audit(&path, "Undo(Undo { no_confirm: true })").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_ratings() {
+        let june = |day| chrono::NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+
+        let mut data = Data::default();
+        for name in ["A", "B", "C"] {
+            data.add_player(name.to_owned(), 0.0);
+        }
+        data.play(play(june(1), [("A", 4), ("B", -2), ("C", -2)]));
+        data.adjust_α_with(AdjustAlpha {
+            value: 0.05,
+            date: Some(june(1)),
+            reason: Some("faster".to_owned()),
+        });
+        data.start_season("summer".to_owned(), Some(june(1)));
+        data.play(play(june(2), [("A", -6), ("B", 2), ("C", 4)]));
+        data.play(play(june(3), [("A", 2), ("B", 2), ("C", -4)]));
+        data.play(play(june(4), [("A", 0), ("B", 3), ("C", -3)]));
+
+        let before = data.evaluate();
+        assert_eq!(data.seasons_cut_by_compaction(june(3)), ["summer"]);
+        assert!(data.seasons_cut_by_compaction(june(2)).is_empty());
+        let removed = data.compact_before(june(3));
+        let after = data.evaluate();
+
+        assert_eq!(removed, 7);
+        assert_eq!(data.history.len(), 3 + 2 + 2);
+        assert_eq!(after.α, before.α);
+        assert_eq!(after.ratings, before.ratings);
+        assert_eq!(after.games_played, before.games_played);
+        assert!(matches!(
+            &data.history[3],
+            Change::AdjustAlpha(AdjustAlpha { reason: Some(reason), .. }) if reason == "faster"
+        ));
+        assert!(data.evaluate_season("summer").is_some());
+        assert_eq!(
+            toml::from_str::<Data>(&toml::to_string(&data).unwrap()).unwrap(),
+            data
+        );
+
+        let snapshot = data.snapshot();
+        assert!(snapshot.evaluate_season("summer").is_none());
+        assert!(snapshot
+            .evaluate()
+            .games_played
+            .values()
+            .all(|games| *games == 0));
+
+        let mut data = Data::default();
+        data.config.auto_center = true;
+        for (name, rating) in [("A", 0.0), ("B", 0.0), ("C", 2.0)] {
+            data.add_player(name.to_owned(), rating);
+        }
+        data.play(play(june(1), [("A", 4), ("B", -2), ("C", -2)]));
+        data.play(play(june(2), [("A", -6), ("B", 2), ("C", 4)]));
+        data.add_player("D".to_owned(), 1.0);
+
+        let before = data.evaluate();
+        data.compact_before(chrono::NaiveDate::from_ymd_opt(2024, 6, 2).unwrap());
+        assert!(data.evaluate().approx_eq(&before, 1e-12));
+        assert!(data.snapshot().evaluate().approx_eq(&before, 1e-12));
+    }
+
+    #[test]
+    fn canonical_outcome_order() {
+        let mut play = Play {
+            game_count: 3,
+            outcomes: [("C", -5), ("A", 4), ("B", 1)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+            ..Default::default()
+        };
+
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.3);
+        data.add_player("B".to_owned(), -0.1);
+        data.add_player("C".to_owned(), 0.7);
+        data.history.push(Change::Play(play.clone()));
+        let unsorted = data.evaluate();
+
+        play.canonicalize();
+        assert_eq!(
+            play.outcomes
+                .clone()
+                .map(|outcome| (outcome.player, outcome.score)),
+            [("A", 4), ("B", 1), ("C", -5)].map(|(player, score)| (player.to_owned(), score))
+        );
+
+        data.history.pop();
+        data.history.push(Change::Play(play));
+        let sorted = data.evaluate();
+
+        assert!(unsorted.approx_eq(&sorted, 1e-12));
+    }
+
+    #[test]
+    fn rating_event_deltas() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.5);
+        data.add_player("B".to_owned(), 0.0);
+        data.add_player("C".to_owned(), 0.0);
+        data.play(play(Default::default(), [("A", 4), ("B", -2), ("C", -2)]));
+        data.adjust_α(0.1);
+        data.play(play(Default::default(), [("A", -3), ("B", 6), ("C", -3)]));
+        data.set_rating("A".to_owned(), 0.25);
+        data.play(play(Default::default(), [("A", 0), ("B", 1), ("C", -1)]));
+
+        let events = data.rating_events("A");
+        let start = events[0].after;
+        let deltas: f64 = events.iter().filter_map(RatingEvent::delta).sum();
+
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.history_index)
+                .collect::<Vec<_>>(),
+            vec![0, 3, 5, 6, 7]
+        );
+        assert_eq!(events[0].delta(), None);
+        assert!((start + deltas - data.evaluate().ratings["A"]).abs() < 1e-12);
+    }
+
+    #[test]
+    fn auto_center() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 1.0);
+        data.add_player("B".to_owned(), 0.0);
+        data.add_player("C".to_owned(), 0.5);
+        data.play(Play {
+            game_count: 2,
+            outcomes: [("A", 4), ("B", -2), ("C", -2)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+            ..Default::default()
+        });
+
+        let raw = data.evaluate();
+        data.config.auto_center = true;
+        let centered = data.evaluate();
+
+        assert!(centered.ratings.values().sum::<f64>().abs() < 1e-12);
+        assert!(raw.ratings.values().sum::<f64>().abs() > 0.1);
+
+        let display =
+            |eval: &Evaluation, player| data.config.rating_to_display(eval.ratings[player]);
+        for (first, second) in [("A", "B"), ("B", "C"), ("A", "C")] {
+            let raw_difference = display(&raw, first) - display(&raw, second);
+            let centered_difference = display(&centered, first) - display(&centered, second);
+
+            assert!((raw_difference - centered_difference).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn repair_from_backup() {
+        let dir = env::temp_dir().join(format!("ultira-repair-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("ultira.toml");
+
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.5);
+
+        write_data(&path, &data).unwrap();
+        assert_eq!(repair(&path).unwrap(), Repair::Intact);
+
+        fs::write(&path, "[config]\nspread = ").unwrap();
+        assert!(repair(&path).is_err());
+
+        write_data(&dir.join("ultira.toml.bak"), &data).unwrap();
+        assert_eq!(
+            repair(&path).unwrap(),
+            Repair::FromBackup(dir.join("ultira.toml.bak"))
+        );
+        assert_eq!(read_data(&path).unwrap(), data);
+        assert!(dir.join("ultira.toml.corrupt").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn exact_name_match_modes() {
+        let eval = Evaluation {
+            ratings: HashMap::from([("Ann".to_owned(), 0.0), ("Anna".to_owned(), 0.0)]),
+            ..Default::default()
+        };
+
+        assert_eq!(eval.matching_names("Ann"), vec!["Ann"]);
+        assert_eq!(
+            eval.matching_names_with("Ann", MatchMode::StrictExact),
+            vec!["Ann"]
+        );
+        assert_eq!(
+            eval.matching_names_with("Ann", MatchMode::PreferPrefix),
+            vec!["Ann", "Anna"]
+        );
+        assert_eq!(
+            eval.matching_names_with("Anna", MatchMode::PreferPrefix),
+            vec!["Anna"]
+        );
+    }
+
+    #[test]
+    fn evaluation_progress() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 0.0);
+        data.adjust_α(0.1);
+
+        let mut calls = Vec::new();
+        let eval = data.evaluate_with_progress(|done| calls.push(done));
+
+        assert_eq!(calls, vec![1, 2, 3]);
+        assert_eq!(eval, data.evaluate());
+    }
+
+    #[test]
+    fn shared_ranks() {
+        let eval = Evaluation {
+            ratings: HashMap::from(
+                [("A", 1.0), ("B", 0.5), ("C", 0.5), ("D", 0.0)]
+                    .map(|(player, rating)| (player.to_owned(), rating)),
+            ),
+            games_played: HashMap::from(
+                [("A", 0), ("B", 3), ("C", 2), ("D", 5)]
+                    .map(|(player, games)| (player.to_owned(), games)),
+            ),
+            ..Default::default()
+        };
+
+        assert_eq!(eval.rank("A", 0), Some((1, 4)));
+        assert_eq!(eval.rank("B", 0), Some((2, 4)));
+        assert_eq!(eval.rank("C", 0), Some((2, 4)));
+        assert_eq!(eval.rank("D", 0), Some((4, 4)));
+        assert_eq!(eval.rank("E", 0), None);
+
+        // The same as the leaderboard of ratings
+        assert_eq!(eval.rank("A", 3), None);
+        assert_eq!(eval.rank("B", 3), Some((1, 2)));
+        assert_eq!(eval.rank("D", 3), Some((2, 2)));
+    }
+
+    #[test]
+    fn play_surprise() {
+        let eval = Evaluation {
+            ratings: HashMap::from(
+                [("A", 2.0), ("B", -1.0), ("C", -1.0)]
+                    .map(|(player, rating)| (player.to_owned(), rating)),
+            ),
+            ..Default::default()
+        };
+        let outcomes = |scores: [i64; 3]| {
+            [0, 1, 2].map(|i| Outcome {
+                player: ["A", "B", "C"][i].to_owned(),
+                score: scores[i],
+            })
+        };
+
+        assert_eq!(eval.predict(["A", "B", "C"], 2.0), [4.0, -2.0, -2.0]);
+        assert_eq!(eval.predict(["A", "B", "C"], 2.5), [5.0, -2.5, -2.5]);
+        assert!(eval.surprise(&outcomes([4, -2, -2]), 2.0) < 1e-12);
+        assert!(eval.surprise(&outcomes([-4, 2, 2]), 2.0) > 2.0);
+    }
+
+    #[test]
+    fn set_change_date() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+
+        let mut data = Data::default();
+        for name in ["A", "B", "C"] {
+            data.add_player(name.to_owned(), 0.0);
+        }
+        data.play(Play {
+            game_count: 1,
+            outcomes: [("A", 2), ("B", -1), ("C", -1)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+            ..Default::default()
+        });
+
+        let before = data.evaluate();
+
+        data.set_date(3, date).unwrap();
+        assert_eq!(data.history[3].date(), Some(date));
+        assert_eq!(data.evaluate(), before);
+
+        assert!(data.set_date(0, date).is_err());
+        assert!(data.set_date(4, date).is_err());
+    }
+
+    #[test]
+    fn deterministic_centering() {
+        let mut data = Data::default();
+        data.config.auto_center = true;
+        for i in 0..50 {
+            data.add_player(format!("P{i}"), 0.1 * i as f64 + 1.0 / (i + 3) as f64);
+        }
+
+        let first = data.evaluate();
+        for _ in 0..10 {
+            let eval = data.evaluate();
+
+            for (player, rating) in &first.ratings {
+                assert_eq!(rating.to_bits(), eval.ratings[player].to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn ratings_table_layouts() {
+        let june = |day| chrono::NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+
+        let mut data = Data::default();
+        for name in ["A", "B", "C"] {
+            data.add_player(name.to_owned(), 0.0);
+        }
+        data.play(play(june(1), [("A", 2), ("B", -1), ("C", -1)]));
+        data.play(play(june(1), [("A", 2), ("B", -1), ("C", -1)]));
+        data.add_player("D".to_owned(), 0.0);
+        data.play(play(june(2), [("B", 2), ("C", -1), ("D", -1)]));
+
+        let wide = data.ratings_table(&TableOptions::default());
+        let wide: Vec<&str> = wide.lines().collect();
+        assert_eq!(wide.len(), 3);
+        assert_eq!(wide[0], "datum\tA\tB\tC\tD");
+        assert!(wide[1].starts_with("2024-06-01\t"));
+        assert!(wide[1].ends_with('\t'));
+
+        let tidy = data.ratings_table(&TableOptions {
+            layout: Layout::Tidy,
+            ..Default::default()
+        });
+        let tidy: Vec<&str> = tidy.lines().collect();
+        assert_eq!(tidy[0], "datum\tplayer\trating");
+        assert_eq!(tidy.len(), 1 + 3 + 4);
+        assert!(tidy[1..].iter().all(|row| row.split('\t').count() == 3));
+
+        let selected = data.ratings_table(&TableOptions {
+            players: Some(vec!["D".to_owned(), "A".to_owned()]),
+            ..Default::default()
+        });
+        let selected: Vec<&str> = selected.lines().collect();
+        assert_eq!(selected[0], "datum\tD\tA");
+        assert!(selected.iter().all(|row| row.split('\t').count() == 3));
+
+        let semicolon = TableOptions {
+            layout: Layout::Tidy,
+            delimiter: ';',
+            decimal_comma: true,
+            ..Default::default()
+        };
+        assert!(semicolon.check().is_ok());
+        let semicolon = data.ratings_table(&semicolon);
+        let semicolon: Vec<&str> = semicolon.lines().collect();
+        assert_eq!(semicolon[0], "datum;player;rating");
+        assert!(semicolon[1].starts_with("2024-06-01;A;"));
+        assert!(semicolon[1..]
+            .iter()
+            .all(|row| row.split(';').count() == 3 && row.contains(',') && !row.contains('\t')));
+
+        let comma = TableOptions {
+            delimiter: ',',
+            ..Default::default()
+        };
+        assert!(comma.check().is_ok());
+        assert!(TableOptions {
+            decimal_comma: true,
+            ..comma
+        }
+        .check()
+        .is_err());
+    }
+
+    #[test]
+    fn config_schema() {
+        let config = Config {
+            spread: 75.0,
+            ..Default::default()
+        };
+
+        let schema = config.schema().unwrap();
+
+        assert!(schema.contains("spread (float) = 75.0 (default: 50.0)"));
+        assert!(schema.contains("base_rating (float) = 100.0 (default: 100.0)"));
+        assert!(schema.contains("starting_alpha"));
+    }
+
+    #[test]
+    fn preview_change() {
+        let mut data = Data::default();
+        data.config.auto_center = true;
+        for (name, rating) in [("A", 0.5), ("B", 0.0), ("C", -0.2)] {
+            data.add_player(name.to_owned(), rating);
+        }
+
+        let change = Change::Play(Play {
+            game_count: 2,
+            outcomes: [("A", -2), ("B", 4), ("C", -2)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+            ..Default::default()
+        });
+
+        let history = data.history.clone();
+        let preview = data.preview(&change);
+        assert_eq!(data.history, history);
+
+        data.history.push(change);
+        assert!(preview.approx_eq(&data.evaluate(), 1e-12));
+    }
+
+    #[test]
+    fn game_count_limit() {
+        let mut config = Config::default();
+
+        assert!(config.check_game_count(12).is_ok());
+        assert!(config.check_game_count(0).is_err());
+        assert!(config.check_game_count(1_000_000_000).is_err());
+
+        config.max_game_count = 10;
+        assert!(config.check_game_count(10).is_ok());
+        assert!(config.check_game_count(11).is_err());
+
+        let mut win = play(Default::default(), [("A", 2), ("B", -1), ("C", -1)]);
+        assert!(config.check_play(&win).is_ok());
+        win.fractional_games = Some(f64::NAN);
+        assert!(config.check_play(&win).is_err());
+        win.fractional_games = None;
+        win.game_count = 0;
+        assert!(config.check_play(&win).is_err());
+        win.game_count = 1;
+        win.outcomes[2].score = 0;
+        assert_eq!(
+            config.check_play(&win).unwrap_err(),
+            "scores of the play sum to 1"
+        );
+    }
+
+    #[test]
+    fn win_probabilities() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+
+        let odds = data.evaluate().win_probabilities(["A", "B", "C"]);
+        assert!(odds.iter().all(|p| (p - 1.0 / 3.0).abs() < 1e-12));
+
+        data.set_rating("A".to_owned(), 1.0);
+        let odds = data.evaluate().win_probabilities(["A", "B", "C"]);
+        assert!(odds[0] > odds[1]);
+        assert!((odds[1] - odds[2]).abs() < 1e-12);
+        assert!((odds.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!((odds[0] / odds[1] - 1f64.exp()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn stored_display_settings() {
+        let mut data = Data::default();
+        data.config.display.decimal_comma = true;
+
+        let data = from_json::<Data>(&to_json(&data).unwrap()).unwrap();
+        let display = data.config.display.with_overrides(None, None, None);
+        assert_eq!(display.format(101.26), " 101,3");
+
+        let display = data
+            .config
+            .display
+            .with_overrides(Some(false), Some(2), None);
+        assert_eq!(display.format(101.26), " 101.26");
+    }
+
+    #[test]
+    fn non_zero_sum_play() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.play(Play::now(
+            1,
+            [("A", 1), ("B", 0), ("C", -1)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+        ));
+        data.play(Play::now(
+            1,
+            [("A", 2), ("B", 1), ("C", -1)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+        ));
+
+        let problems = data.check_zero_sum();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::NonZeroSum);
+        assert_eq!(problems[0].history_index, Some(4));
+        assert!(problems[0].message.ends_with("sum to 2"));
+    }
+
+    #[test]
+    fn third_score_inference() {
+        assert_eq!(inferred_score([4, -2]), Some(-2));
+        assert_eq!(inferred_score([0, 0]), Some(0));
+        assert_eq!(inferred_score([i64::MAX, 1]), None);
+        assert_eq!(inferred_score([i64::MIN, 0]), None);
+    }
+
+    #[test]
+    fn clamped_rating_change() {
+        let mut data = Data::default();
+        data.config.max_rating_change_per_play = Some(5.0);
+        data.adjust_score_multiplier(0.5);
+        for player in ["A", "B", "C"] {
+            data.add_player_display(player.to_owned(), 100.0).unwrap();
+        }
+
+        let win = Play {
+            game_count: 10,
+            ..play(Default::default(), [("A", 1000), ("B", -500), ("C", -500)])
+        };
+        assert_eq!(
+            data.evaluate().clamped_players(&win, &data.config),
+            ["A", "B", "C"]
+        );
+        data.play(win);
+
+        let eval = data.evaluate();
+        let display = |player: &str| data.config.rating_to_display(eval.ratings[player]);
+        assert!((display("A") - 105.0).abs() < 1e-9);
+        assert!((display("B") - 95.0).abs() < 1e-9);
+        assert!((display("C") - 95.0).abs() < 1e-9);
+
+        data.config.max_rating_change_per_play = None;
+        assert!(data.config.rating_to_display(data.evaluate().ratings["A"]) > 105.0);
+    }
+
+    #[test]
+    fn season_standings() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C", "D"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.play(Play {
+            game_count: 2,
+            ..play(Default::default(), [("A", 2), ("B", -1), ("D", -1)])
+        });
+        data.start_season("spring".to_owned(), None);
+        data.play(Play {
+            game_count: 2,
+            ..play(Default::default(), [("A", 2), ("B", -1), ("C", -1)])
+        });
+
+        let data = from_json::<Data>(&to_json(&data).unwrap()).unwrap();
+        assert!(data.evaluate_season("autumn").is_none());
+
+        let global = data.evaluate();
+        let season = data.evaluate_season("spring").unwrap();
+        assert_ne!(season.ratings, global.ratings);
+        assert!(season.ratings["A"] < global.ratings["A"]);
+        assert!(season.ratings["D"] > global.ratings["D"]);
+        assert!(season.ratings["A"] > season.ratings["D"]);
+        assert_eq!(global.games_played["A"], 4);
+        assert_eq!(season.games_played["A"], 2);
+        assert_eq!(global.leaderboard(1).len(), 4);
+        assert_eq!(season.leaderboard(1).len(), 3);
+    }
+
+    #[test]
+    fn generated_data() {
+        use rand::SeedableRng;
+
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let end = chrono::NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let generate = |seed| {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            Data::generate(6, 50, start..=end, &mut rng).unwrap()
+        };
+
+        let data = generate(7);
+        assert_eq!(data, generate(7));
+        assert_ne!(data, generate(8));
+        assert_eq!(data.history.len(), 56);
+        assert!(data.check().is_empty());
+        assert!(data.check_zero_sum().is_empty());
+        assert!(data.check_conservation(1e-9).is_empty());
+        assert!(data
+            .history
+            .iter()
+            .filter_map(Change::date)
+            .all(|date| (start..=end).contains(&date)));
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        assert!(Data::generate(2, 1, start..=end, &mut rng).is_err());
+    }
+
+    #[test]
+    fn timed_evaluation() {
+        use rand::SeedableRng;
+
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let mut data = Data::generate(5, 20, start..=start, &mut rng).unwrap();
+        data.config.auto_center = true;
+
+        let (eval, timings) = data.evaluate_timed();
+        assert_eq!(eval, data.evaluate());
+        assert_eq!(timings.changes, data.history.len());
+        assert!(timings.plays + timings.other_changes + timings.centering <= timings.total);
+    }
+
+    #[test]
+    fn play_note() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        let win = Play {
+            game_count: 3,
+            ..play(Default::default(), [("A", 3), ("B", 0), ("C", -3)])
+        };
+        data.play(win.clone());
+        let without_note = data.evaluate();
+
+        data.history.pop();
+        data.play(Play {
+            note: Some("tournament final".to_owned()),
+            ..win
+        });
+        assert_eq!(data.evaluate(), without_note);
+
+        let toml = toml::to_string(&data).unwrap();
+        assert!(toml.contains("note = \"tournament final\""));
+        assert_eq!(toml::from_str::<Data>(&toml).unwrap(), data);
+        assert_eq!(from_json::<Data>(&to_json(&data).unwrap()).unwrap(), data);
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+
+        let toml = format!("\u{feff}{}", toml::to_string(&data).unwrap());
+        assert_eq!(parse_data(&toml, Format::Toml).unwrap(), data);
+
+        let json = format!("\u{feff}{}", to_json(&data).unwrap());
+        assert!(from_json::<Data>(&json).is_err());
+        assert_eq!(parse_data(&json, Format::Json).unwrap(), data);
+    }
+
+    #[test]
+    fn fixed_alpha() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.adjust_α(0.01);
+        for scores in [[3, 0, -3], [2, 1, -3], [3, -1, -2]] {
+            data.play(Play::now(
+                3,
+                [0, 1, 2].map(|i| Outcome {
+                    player: ["A", "B", "C"][i].to_owned(),
+                    score: scores[i],
+                }),
+            ));
+        }
+
+        let spread = |eval: Evaluation| {
+            let ratings = eval.ratings.values();
+            ratings.clone().copied().fold(f64::NEG_INFINITY, f64::max)
+                - ratings.copied().fold(f64::INFINITY, f64::min)
+        };
+
+        let fixed = data.with_fixed_α(0.01);
+        assert_eq!(fixed.history.len(), data.history.len());
+        assert_eq!(fixed.evaluate(), data.evaluate());
+        assert!(spread(data.with_fixed_α(0.1).evaluate()) > spread(data.evaluate()));
+    }
+
+    #[test]
+    fn evaluation_prefix() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.play(Play::now(
+            1,
+            [("A", 2), ("B", -1), ("C", -1)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+        ));
+
+        assert!(data.evaluate_up_to(0).ratings.is_empty());
+        assert_eq!(data.evaluate_up_to(1).ratings.len(), 1);
+        assert_eq!(data.evaluate_up_to(3).ratings["A"], 0.0);
+        assert!(data.evaluate_up_to(4).ratings["A"] > 0.0);
+        assert_eq!(data.evaluate_up_to(4), data.evaluate());
+        assert_eq!(data.evaluate_up_to(100), data.evaluate());
+    }
+
+    #[test]
+    fn non_finite_rating() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 0.0);
+        let toml = toml::to_string(&data).unwrap();
+        assert!(parse_data(&toml, Format::Toml).is_ok());
+
+        let (before, after) = toml.rsplit_once("rating = 0.0").unwrap();
+        let nan = format!("{before}rating = nan{after}");
+
+        let err = parse_data(&nan, Format::Toml).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "history[1].rating is NaN, only finite numbers are allowed"
+        );
+
+        data.config.rating_floor = Some(f64::NEG_INFINITY);
+        assert_eq!(
+            data.check_finite().unwrap_err(),
+            "config.rating_floor is -inf, only finite numbers are allowed"
+        );
+        let path = env::temp_dir().join(format!("ultira-finite-test-{}.toml", std::process::id()));
+        assert!(write_data(&path, &data).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn relative_ratings() {
+        let mut data = Data::default();
+        data.add_player_display("A".to_owned(), 100.0).unwrap();
+        data.add_player_display("B".to_owned(), 102.0).unwrap();
+        data.add_player_display("C".to_owned(), 97.5).unwrap();
+
+        let mut eval = data.evaluate();
+        eval.anchor_at("A");
+        let display = data.config.display;
+        let relative = |player: &str| eval.ratings[player] * data.config.spread;
+
+        assert_eq!(eval.ratings["A"], 0.0);
+        assert_eq!(display.format_signed(relative("B")), "  +2.0");
+        assert_eq!(display.format_signed(relative("C")), "  -2.5");
+    }
+
+    #[test]
+    fn elo_import() {
+        let mut data = Data::default();
+        let count = data
+            .import_elo("name,elo\nAnna,1600\nBence, 1450\n", 1500.0, 200.0)
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let eval = data.evaluate();
+        let display = |player: &str| data.config.rating_to_display(eval.ratings[player]);
+        assert!((display("Anna") - 125.0).abs() < 1e-9);
+        assert!((display("Bence") - 87.5).abs() < 1e-9);
+
+        let err = data
+            .import_elo("Csaba,1500\nDori,abc\n", 1500.0, 200.0)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "line 2: 'abc' is not a number");
+        assert_eq!(data.history.len(), 2);
+    }
+
+    #[test]
+    fn player_references() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C", "D"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.play(play(Default::default(), [("A", 2), ("B", -1), ("C", -1)]));
+        data.adjust_α(0.03);
+        data.play(play(Default::default(), [("B", 2), ("C", -1), ("D", -1)]));
+        data.set_rating("A".to_owned(), 1.0);
+        data.start_season("spring".to_owned(), None);
+        data.play(play(Default::default(), [("D", 2), ("A", -1), ("B", -1)]));
+
+        assert_eq!(data.references("A"), [0, 4, 7, 9]);
+        assert_eq!(data.references("C"), [2, 4, 6]);
+        assert!(data.references("E").is_empty());
+    }
+
+    #[test]
+    fn adjacent_duplicates() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        let win = play(Default::default(), [("A", 2), ("B", -1), ("C", -1)]);
+        data.play(win.clone());
+        data.play(win.clone());
+        data.set_rating("A".to_owned(), 0.1);
+        data.set_rating("A".to_owned(), 0.1 + 1e-12);
+        data.set_rating("A".to_owned(), 0.2);
+        data.play(win);
+
+        assert_eq!(data.dedup_adjacent(1e-9), 2);
+        assert_eq!(data.history.len(), 7);
+        assert_eq!(data.references("B"), [1, 3, 6]);
+        assert_eq!(data.dedup_adjacent(1e-9), 0);
+
+        let interrupted = |games| {
+            let mut play = play(Default::default(), [("A", 2), ("B", -1), ("C", -1)]);
+            play.set_games(games).unwrap();
+            Change::Play(play)
+        };
+        assert!(interrupted(2.5).approx_eq(&interrupted(2.5 + 1e-12), 1e-9));
+        assert!(!interrupted(2.5).approx_eq(&interrupted(2.6), 1e-9));
+        assert!(!interrupted(2.5).approx_eq(&interrupted(3.0), 1e-9));
+    }
+
+    #[test]
+    fn rating_sparklines() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C", "D"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        for scores in [[2, -1, -1], [2, -1, -1], [-2, 1, 1]] {
+            data.play(Play::now(
+                1,
+                [0, 1, 2].map(|i| Outcome {
+                    player: ["A", "B", "C"][i].to_owned(),
+                    score: scores[i],
+                }),
+            ));
+        }
+
+        let a = data.sparkline("A");
+        assert_eq!(a.chars().count(), data.rating_events("A").len());
+        assert_eq!(a, "▁▅█▄");
+        assert_eq!(data.sparkline("D"), "▄");
+        assert_eq!(data.sparkline("E"), "");
+    }
+
+    #[test]
+    fn self_rename() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.config.player_alpha.insert("A".to_owned(), 2.0);
+        data.play(play(Default::default(), [("A", 2), ("B", -1), ("C", -1)]));
+        let before = toml::to_string(&data).unwrap();
+
+        data.rename("A", "A");
+        assert_eq!(toml::to_string(&data).unwrap(), before);
+
+        data.rename("A", "D");
+        let mut recorded = play(Default::default(), [("D", 2), ("B", -1), ("C", -1)]);
+        recorded.canonicalize();
+        assert!(data.history[3].approx_eq(&Change::Play(recorded), 1e-9));
+    }
+
+    #[test]
+    fn appended_ratings_table() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        let january = |day| chrono::NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        data.play(play(january(1), [("A", 2), ("B", -1), ("C", -1)]));
+        data.play(play(january(2), [("A", -1), ("B", 2), ("C", -1)]));
+
+        for layout in [Layout::Wide, Layout::Tidy] {
+            let options = TableOptions {
+                layout,
+                ..Default::default()
+            };
+            let mut data = data.clone();
+            let existing = data.ratings_table(&options);
+            assert_eq!(data.ratings_table_append(&options, &existing).unwrap(), "");
+
+            data.play(play(january(3), [("A", -1), ("B", -1), ("C", 2)]));
+            data.play(play(january(4), [("A", 2), ("B", -1), ("C", -1)]));
+            data.play(play(january(4), [("A", 0), ("B", 2), ("C", -2)]));
+
+            let appended = data.ratings_table_append(&options, &existing).unwrap();
+            let rows_per_point = if layout == Layout::Wide { 1 } else { 3 };
+            assert_eq!(appended.lines().count(), 2 * rows_per_point);
+            assert_eq!(existing + &appended, data.ratings_table(&options));
+
+            let existing = data.ratings_table(&options);
+            data.play(play(january(4), [("A", -2), ("B", 4), ("C", -2)]));
+            assert!(data.ratings_table_append(&options, &existing).is_err());
+        }
+
+        let existing = data.ratings_table(&TableOptions::default());
+        let tidy = TableOptions {
+            layout: Layout::Tidy,
+            ..Default::default()
+        };
+        assert!(data.ratings_table_append(&tidy, &existing).is_err());
+        data.history.truncate(4);
+        assert!(data
+            .ratings_table_append(&TableOptions::default(), &existing)
+            .is_err());
+    }
 
     #[test]
-    fn name_matching() {
-        assert!(match_names("Németh Marcell", "Németh M"));
-        assert!(match_names("Németh Márton", "Németh M"));
-        assert!(!match_names("Németh Dominik", "Németh M"));
-        assert!(match_names("Németh Marcell", "Ma"));
-        assert!(!match_names("Németh Márton", "Ma"));
+    fn balanced_triple() {
+        let mut data = Data::default();
+        for (player, rating) in [("A", 0.0), ("B", 1.0), ("C", 1.5), ("D", 1.8)] {
+            data.add_player(player.to_owned(), rating);
+        }
+        let eval = data.evaluate();
+
+        let mut triple = eval.most_balanced(&["A", "B", "C", "D"]).unwrap();
+        triple.sort_unstable();
+        assert_eq!(triple, ["B", "C", "D"]);
+
+        let mut triple = eval.most_balanced(&["D", "A", "C"]).unwrap();
+        triple.sort_unstable();
+        assert_eq!(triple, ["A", "C", "D"]);
+
+        assert!(eval.most_balanced(&["A", "B"]).is_none());
+    }
+
+    #[test]
+    fn data_versions() {
+        let data = Data::default();
+        let toml = toml::to_string(&data).unwrap();
+        assert!(toml.starts_with(&format!("version = {DATA_VERSION}\n")));
+
+        let unversioned = toml.replacen(&format!("version = {DATA_VERSION}\n"), "", 1);
+        assert_eq!(parse_data(&unversioned, Format::Toml).unwrap(), data);
+
+        let future = format!(
+            "version = {}\n[config]\nunknown = 1\n[[history.unknown_change]]\n",
+            DATA_VERSION + 1
+        );
+        let err = parse_data(&future, Format::Toml).unwrap_err();
+        assert!(err.to_string().contains("newer ultira"));
+        assert!(err.to_string().contains("upgrade"));
+    }
+
+    #[test]
+    fn watched_file() {
+        let dir = env::temp_dir().join(format!("ultira-watch-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("ultira.toml");
+
+        let mut data = Data::default();
+        write_data(&path, &data).unwrap();
+
+        let writer = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                write_atomically(&path, b"[config]\nspread = ").unwrap();
+                std::thread::sleep(Duration::from_millis(50));
+                data.add_player("A".to_owned(), 0.5);
+                write_data(&path, &data).unwrap();
+            })
+        };
+
+        let mut players = Vec::new();
+        let mut errors = 0;
+        watch(&path, Duration::from_millis(5), |result| {
+            match result {
+                Ok(data) => players.push(data.evaluate().ratings.len()),
+                Err(_) => errors += 1,
+            }
+            players.len() < 2
+        });
+        writer.join().unwrap();
+
+        assert_eq!(players, [0, 1]);
+        assert_eq!(errors, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn toml_comments_kept() {
+        let dir = env::temp_dir().join(format!("ultira-comments-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("ultira.toml");
+
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.0);
+        data.add_player("B".to_owned(), 0.0);
+        let toml = toml::to_string(&data)
+            .unwrap()
+            .replacen("[config]", "# Our league\n[config]", 1)
+            .replacen("spread = 50.0", "spread = 50.0 # agreed in 2023", 1)
+            .replacen("[[history]]", "# First player\n[[history]]", 1);
+        fs::write(&path, toml).unwrap();
+
+        let mut data = read_data(&path).unwrap();
+        data.config.spread = 60.0;
+        data.add_player("C".to_owned(), 0.0);
+        write_data(&path, &data).unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.contains("# Our league\n[config]"));
+        assert!(written.contains("spread = 60.0 # agreed in 2023"));
+        assert!(
+            written.contains("# First player\n[[history]]\n\n[history.add_player]\nname = \"A\"")
+        );
+        assert!(written.find("name = \"B\"") < written.find("name = \"C\""));
+        assert_eq!(read_data(&path).unwrap(), data);
+
+        data.history.truncate(1);
+        write_data(&path, &data).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.contains("# First player"));
+        assert!(!written.contains("name = \"B\""));
+        assert_eq!(read_data(&path).unwrap(), data);
+
+        // Comments stay with their entry when earlier ones are removed
+        let mut data = Data::default();
+        for name in ["A", "B", "C"] {
+            data.add_player(name.to_owned(), 0.0);
+        }
+        let toml = toml::to_string(&data).unwrap().replacen(
+            "[[history]]\n\n[history.add_player]\nname = \"C\"",
+            "# Third player\n[[history]]\n\n[history.add_player]\nname = \"C\"",
+            1,
+        );
+        fs::write(&path, toml).unwrap();
+        data.history.remove(0);
+        write_data(&path, &data).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(
+            written.contains("# Third player\n[[history]]\n\n[history.add_player]\nname = \"C\"")
+        );
+        assert_eq!(read_data(&path).unwrap(), data);
+
+        // An empty history is written as a key, which must not leak into the first table header
+        write_data(&path, &Data::default()).unwrap();
+        let empty = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("# Our league\n{empty}")).unwrap();
+        data.history.truncate(1);
+        write_data(&path, &data).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.contains("# Our league\n"));
+        assert!(written.contains("\n[[history]]\n"));
+        assert_eq!(read_data(&path).unwrap(), data);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rating_floor() {
+        let mut data = Data::default();
+        data.config.rating_floor = Some(95.0);
+        for player in ["A", "B", "C"] {
+            data.add_player_display(player.to_owned(), 100.0).unwrap();
+        }
+        for _ in 0..5 {
+            data.play(Play::now(
+                10,
+                [("A", 20), ("B", 0), ("C", -20)].map(|(player, score)| Outcome {
+                    player: player.to_owned(),
+                    score,
+                }),
+            ));
+        }
+
+        let eval = data.evaluate();
+        assert!(data.config.rating_from_display(95.0) > eval.ratings["C"]);
+        assert_eq!(
+            data.config.rating_to_listed_display(eval.ratings["C"]),
+            95.0
+        );
+        assert!(data.config.rating_to_display(eval.ratings["C"]) < 95.0);
+        assert!(data.config.rating_to_listed_display(eval.ratings["A"]) > 100.0);
+        assert!(data.config.check_display_range().is_ok());
+
+        data.config.rating_ceiling = Some(90.0);
+        assert!(data.config.check_display_range().is_err());
+        data.config.rating_ceiling = None;
+
+        data.config.clamp_internal_ratings = true;
+        let clamped = data.evaluate();
+        assert!((clamped.ratings["C"] - data.config.rating_from_display(95.0)).abs() < 1e-12);
+        assert!(clamped.ratings["A"] > eval.ratings["A"]);
+    }
+
+    #[test]
+    fn pick_option() {
+        let options = ["Anna", "Anne", "Annabel"];
+        let pick = |input: &str| pick(&options, &mut io::Cursor::new(input));
+
+        assert_eq!(pick("2\n"), Some("Anne"));
+        assert_eq!(pick(" 3 \n"), Some("Annabel"));
+        assert_eq!(pick("0\n"), None);
+        assert_eq!(pick("4\n"), None);
+        assert_eq!(pick("Anna\n"), None);
+        assert_eq!(pick(""), None);
+    }
+
+    #[test]
+    fn annotated_alpha_adjustment() {
+        let old = "[config]\nspread = 50.0\nbase_rating = 100.0\nstarting_alpha = 0.02\n\n[[history]]\nadjust_alpha = 0.05\n";
+        let data = parse_data(old, Format::Toml).unwrap();
+        assert_eq!(
+            data.history,
+            [Change::AdjustAlpha(AdjustAlpha {
+                value: 0.05,
+                date: None,
+                reason: None,
+            })]
+        );
+        assert_eq!(data.evaluate().α, 0.05);
+
+        let mut data = Data::default();
+        data.adjust_α_with(AdjustAlpha {
+            value: 0.04,
+            date: chrono::NaiveDate::from_ymd_opt(2024, 3, 1),
+            reason: Some("ratings moved too slowly".to_owned()),
+        });
+        assert_eq!(
+            data.history[0].date(),
+            chrono::NaiveDate::from_ymd_opt(2024, 3, 1)
+        );
+
+        let toml = toml::to_string(&data).unwrap();
+        assert!(toml.contains("date = 2024-03-01"));
+        assert_eq!(parse_data(&toml, Format::Toml).unwrap(), data);
+        assert_eq!(
+            parse_data(&to_json(&data).unwrap(), Format::Json).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn movers() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C", "D"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+
+        let month = |month| chrono::NaiveDate::from_ymd_opt(2024, month, 1).unwrap();
+
+        data.play(play(month(1), [("A", -5), ("B", 5), ("C", 0)]));
+        data.play(play(month(2), [("A", 6), ("B", -6), ("C", 0)]));
+        data.play(play(month(3), [("A", 3), ("B", -3), ("C", 0)]));
+
+        let since = chrono::NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let movers = data.movers(since);
+        let start = data.evaluate_before(since);
+        let end = data.evaluate();
+
+        assert_eq!(movers.len(), 4);
+        assert_eq!(movers[0].0, "A");
+        assert!(movers[0].1 > 0.0);
+        assert_eq!(movers[3].0, "B");
+        assert!(movers[3].1 < 0.0);
+        assert!(
+            (movers[0].1 - (end.ratings["A"] - start.ratings["A"]) * data.config.spread).abs()
+                < 1e-9
+        );
+        assert_eq!(
+            movers.iter().find(|(player, _)| player == "D").unwrap().1,
+            0.0
+        );
+
+        data.add_player("E".to_owned(), 1.0);
+        assert!(data.movers(since).iter().all(|(player, _)| player != "E"));
+    }
+
+    #[test]
+    fn empty_names() {
+        assert!(check_name("").is_err());
+        assert!(check_name("  ").is_err());
+        assert!(check_name("Ann").is_ok());
+
+        let mut data = Data::default();
+        assert!(data.import_elo(",1500\nAnn,1500", 1500.0, 400.0).is_err());
+        assert!(data.history.is_empty());
+
+        data.add_player("Ann".to_owned(), 0.0);
+        data.add_player("Bob".to_owned(), 0.0);
+        let eval = data.evaluate();
+        assert!(eval.matching_names("   ").is_empty());
+        assert!(eval
+            .matching_names_with("", MatchMode::PreferPrefix)
+            .is_empty());
+        assert!(data.check().is_empty());
+
+        data.add_player(" ".to_owned(), 0.0);
+        let problems = data.check();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::EmptyName);
+        assert_eq!(problems[0].history_index, Some(2));
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut data = Data::default();
+        data.config.auto_center = true;
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.add_player("D".to_owned(), 0.5);
+        data.play(Play::now(
+            2,
+            [("A", 4), ("B", -1), ("C", -3)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+        ));
+        data.adjust_α(0.04);
+
+        let snapshot = data.snapshot();
+        assert_eq!(snapshot.config, data.config);
+        assert!(snapshot
+            .history
+            .iter()
+            .all(|change| matches!(change, Change::AddPlayer(_) | Change::AdjustAlpha(_))));
+
+        let eval = data.evaluate();
+        let snapshot_eval = snapshot.evaluate();
+        assert_eq!(snapshot_eval.α, eval.α);
+        assert_eq!(snapshot_eval.ratings.len(), eval.ratings.len());
+        for (player, rating) in &eval.ratings {
+            assert!((snapshot_eval.ratings[player] - rating).abs() < 1e-12);
+            assert_eq!(
+                snapshot_eval.games_played.get(player).copied().unwrap_or(0),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn file_lock() {
+        let path = env::temp_dir().join(format!("ultira-lock-test-{}.toml", std::process::id()));
+
+        let held = lock(&path, false).unwrap();
+        let err = lock(&path, false).unwrap_err();
+        assert!(err.to_string().contains("is locked"));
+
+        let waiting = std::thread::spawn({
+            let path = path.clone();
+            move || lock(&path, true).is_ok()
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!waiting.is_finished());
+
+        drop(held);
+        assert!(waiting.join().unwrap());
+        assert!(lock(&path, false).is_ok());
+
+        let mut lock_path = path.into_os_string();
+        lock_path.push(".lock");
+        fs::remove_file(lock_path).unwrap();
+
+        let missing = env::temp_dir().join(format!("ultira-missing-{}", std::process::id()));
+        let err = lock(&missing.join("ultira.toml"), false).unwrap_err();
+        assert!(err.to_string().starts_with("can't lock"));
+        assert!(!err.to_string().contains("--wait-for-lock"));
+    }
+
+    #[test]
+    fn standings_markdown() {
+        let mut data = Data::default();
+        data.config.ranking_min_games = 1;
+        for player in ["A|B", "C", "D", "E"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.play(Play::now(
+            1,
+            [("A|B", 3), ("C", -3), ("D", 0)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+        ));
+
+        let table = data.standings_markdown();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "| Rank | Player | Rating | Games |");
+        assert_eq!(lines[1], "|---:|:---|---:|---:|");
+        // E has no games yet
+        assert_eq!(lines.len(), 2 + 3);
+
+        for line in &lines {
+            let cells: Vec<&str> = line
+                .strip_prefix('|')
+                .and_then(|line| line.strip_suffix('|'))
+                .unwrap()
+                .split('|')
+                .collect();
+            let escapes = line.matches("\\|").count();
+            assert_eq!(cells.len() - escapes, 4, "{line}");
+        }
+
+        assert!(lines[2].starts_with("| 1 | A\\|B | "));
+        assert!(lines[2].ends_with(" | 1 |"));
+        assert!(lines[4].starts_with("| 3 | C | "));
+    }
+
+    #[test]
+    fn store_precision() {
+        let mut data = Data::default();
+        data.config.store_precision = Some(4);
+        data.add_player("A".to_owned(), 1.0 / 3.0);
+        data.add_player("B".to_owned(), -0.123456789);
+        data.set_rating("A".to_owned(), 2.0 / 7.0);
+
+        let path = env::temp_dir().join(format!(
+            "ultira-store-precision-test-{}.toml",
+            std::process::id()
+        ));
+        write_data(&path, &data).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let stored = parse_data(&written, Format::Toml).unwrap();
+        assert_eq!(stored.history.len(), 3);
+        for line in written.lines().filter(|line| line.starts_with("rating = ")) {
+            let decimals = line
+                .split_once('.')
+                .map_or(0, |(_, decimals)| decimals.len());
+            assert!(decimals <= 4, "{line}");
+        }
+        assert_eq!(stored.evaluate().ratings["B"], -0.1235);
+
+        // The data itself keeps full precision
+        assert_eq!(data.evaluate().ratings["B"], -0.123456789);
+    }
+
+    #[test]
+    fn α_at() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        let win = play(Default::default(), [("A", 2), ("B", -1), ("C", -1)]);
+        data.play(win.clone());
+        data.adjust_α(0.05);
+        data.play(win);
+
+        let starting = data.config.starting_alpha;
+        assert_eq!(data.α_at(0), starting);
+        assert_eq!(data.α_at(3), starting);
+        assert_eq!(data.α_at(4), starting);
+        assert_eq!(data.α_at(5), 0.05);
+        assert_eq!(data.α_at(100), 0.05);
+
+        for i in 0..=data.history.len() {
+            assert_eq!(data.α_at(i), data.evaluate_up_to(i).α);
+        }
+    }
+
+    #[test]
+    fn verify_write() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.1 + 0.2);
+        data.add_player("B".to_owned(), 1.0 / 3.0);
+
+        let path = env::temp_dir().join(format!("ultira-verify-test-{}.toml", std::process::id()));
+        write_data(&path, &data).unwrap();
+        super::verify_write(&path, &data).unwrap();
+
+        // Writes the ratings with fewer digits than they have
+        let mut lossy = data.clone();
+        lossy.config.store_precision = Some(2);
+        write_data(&path, &lossy).unwrap();
+        let err = super::verify_write(&path, &data).unwrap_err();
+        assert!(err.to_string().contains("rating of 'A'"), "{err}");
+
+        // Unless the rounding is configured
+        super::verify_write(&path, &lossy).unwrap();
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parse_play_log() {
+        let log = "# June\n2024-06-01 4 Alice 4 Bob -2 Carol -2\n\n2024-06-02 2 Németh Márton 3 Bob -1 Carol -2\n";
+        let plays = super::parse_play_log(log).unwrap();
+
+        assert_eq!(plays.len(), 2);
+        assert_eq!(plays[0].0, 2);
+        assert_eq!(plays[1].0, 4);
+
+        let play = &plays[1].1;
+        assert_eq!(
+            play.date,
+            chrono::NaiveDate::from_ymd_opt(2024, 6, 2).unwrap()
+        );
+        assert_eq!(play.game_count, 2);
+        assert_eq!(play.outcomes[0].player, "Németh Márton");
+        assert_eq!(play.outcomes.each_ref().map(|o| o.score), [3, -1, -2]);
+
+        for (log, message) in [
+            (
+                "2024-06-01 4 Alice 4 Bob -2 Carol -1",
+                "line 1: scores sum to 1",
+            ),
+            ("2024-06-01 4 Alice 4 Bob -4", "line 1: expected"),
+            (
+                "ok\n2024-06-01 Alice 4 Bob -2 Carol -2",
+                "line 1: expected a date",
+            ),
+            (
+                "2024-06-01 4 Alice 4 Bob -2 Carol -2\n2024-06-01 x",
+                "line 2: expected a game count",
+            ),
+            (
+                "2024-06-01 4 Alice 4 Bob -2 Carol -2 Dave",
+                "line 1: expected",
+            ),
+        ] {
+            let err = super::parse_play_log(log).unwrap_err();
+            assert!(err.to_string().starts_with(message), "{err}");
+        }
+    }
+
+    #[test]
+    fn head_to_head() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C", "D"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+
+        let may = |year| chrono::NaiveDate::from_ymd_opt(year, 5, 1).unwrap();
+        data.play(Play {
+            game_count: 2,
+            ..play(may(2023), [("A", 4), ("B", -2), ("C", -2)])
+        });
+        data.play(Play {
+            game_count: 2,
+            ..play(may(2023), [("B", 1), ("C", 1), ("A", -2)])
+        });
+        data.play(Play {
+            game_count: 2,
+            ..play(may(2024), [("A", -3), ("B", 3), ("D", 0)])
+        });
+        data.play(Play {
+            game_count: 2,
+            ..play(may(2024), [("A", 5), ("C", -5), ("D", 0)])
+        });
+
+        let all = data.head_to_head("A", "B", GroupBy::None);
+        assert_eq!(all.len(), 1);
+        assert_eq!(
+            all["all"],
+            HeadToHead {
+                plays: 3,
+                games: 6,
+                scores: [-1, 2],
+            }
+        );
+        assert_eq!(all["all"].balance(), -3);
+
+        let by_year = data.head_to_head("A", "B", GroupBy::Year);
+        assert_eq!(by_year.keys().collect::<Vec<_>>(), ["2023", "2024"]);
+        assert_eq!(by_year["2023"].scores, [2, -1]);
+        assert_eq!(by_year["2023"].balance(), 3);
+        assert_eq!(by_year["2024"].plays, 1);
+        assert_eq!(by_year["2024"].balance(), -6);
+
+        assert!(data
+            .head_to_head("B", "D", GroupBy::Year)
+            .contains_key("2024"));
+        assert!(data.head_to_head("C", "B", GroupBy::Year)["2023"].balance() == 0);
+    }
+
+    #[test]
+    fn check_determinism() {
+        use rand::SeedableRng;
+
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let end = chrono::NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let mut data = Data::generate(
+            20,
+            200,
+            start..=end,
+            &mut rand::rngs::StdRng::seed_from_u64(7),
+        )
+        .unwrap();
+        data.config.auto_center = true;
+
+        assert_eq!(data.check_determinism(), []);
+    }
+
+    #[test]
+    fn player_names() {
+        use rand::SeedableRng;
+
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut data = Data::generate(
+            8,
+            30,
+            start..=start,
+            &mut rand::rngs::StdRng::seed_from_u64(3),
+        )
+        .unwrap();
+        data.set_rating("Player 1".to_owned(), 0.5);
+        data.start_season("Spring".to_owned(), None);
+        data.adjust_α(0.03);
+
+        let names = data.player_names();
+        let evaluated: BTreeSet<String> = data.evaluate().ratings.into_keys().collect();
+        assert_eq!(names, evaluated);
+        assert_eq!(names.len(), 8);
+    }
+
+    #[test]
+    fn inflation() {
+        use rand::SeedableRng;
+
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let end = chrono::NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let mut data = Data::generate(
+            10,
+            100,
+            start..=end,
+            &mut rand::rngs::StdRng::seed_from_u64(5),
+        )
+        .unwrap();
+        data.config.auto_center = true;
+
+        let inflation = data.inflation();
+        assert!(inflation.len() > 1);
+        assert!(inflation.iter().all(|(_, average)| average.abs() < 1e-9));
+
+        let previous = data.evaluate().ratings["Player 1"];
+        data.set_rating("Player 1".to_owned(), 1.0);
+        data.play(Play {
+            game_count: 1,
+            date: end,
+            outcomes: [1, 2, 3].map(|i| Outcome {
+                player: format!("Player {i}"),
+                score: 0,
+            }),
+            note: None,
+            fractional_games: None,
+            session: None,
+        });
+        let expected = (1.0 - previous) / 10.0;
+        assert!((data.inflation().last().unwrap().1 - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn narrative() {
+        let config = Config::default();
+        let mut play = Play {
+            game_count: 4,
+            date: chrono::NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+            outcomes: [("Alice", 4), ("Bob", -2), ("Carol", -2)].map(|(player, score)| Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+            note: None,
+            fractional_games: None,
+            session: None,
+        };
+        assert_eq!(
+            Change::Play(play.clone()).narrative(&config),
+            "On 2024-06-01, Alice, Bob, and Carol played 4 games; scores 4, -2, -2."
+        );
+
+        play.game_count = 1;
+        play.note = Some("final".to_owned());
+        assert_eq!(
+            Change::Play(play).narrative(&config),
+            "On 2024-06-01, Alice, Bob, and Carol played 1 game; scores 4, -2, -2 (final)."
+        );
+
+        assert_eq!(
+            Change::AddPlayer(AddPlayer {
+                name: "dave".to_owned(),
+                rating: 0.0,
+                games_played: 0,
+            })
+            .narrative(&config),
+            "dave joined with a rating of 100.0."
+        );
+        assert_eq!(
+            Change::SeasonBoundary(SeasonBoundary {
+                name: "Spring".to_owned(),
+                date: None,
+            })
+            .narrative(&config),
+            "Season Spring started."
+        );
+    }
+
+    #[test]
+    fn evaluation_approx_eq() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 0.5);
+        data.add_player("B".to_owned(), -0.5);
+
+        let eval = data.evaluate();
+        let mut nudged = eval.clone();
+        *nudged.ratings.get_mut("A").unwrap() += 1e-12;
+
+        assert_ne!(eval, nudged);
+        assert!(eval.approx_eq(&nudged, 1e-9));
+        assert!(!eval.approx_eq(&nudged, 1e-13));
+
+        nudged.α += 1e-6;
+        assert!(!eval.approx_eq(&nudged, 1e-9));
+
+        let mut extra = eval.clone();
+        extra.ratings.insert("C".to_owned(), 0.0);
+        assert!(!eval.approx_eq(&extra, 1.0));
+        assert!(!extra.approx_eq(&eval, 1.0));
+    }
+
+    #[test]
+    fn active_players() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C", "D"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+
+        let month = |month| chrono::NaiveDate::from_ymd_opt(2024, month, 1).unwrap();
+        data.play(play(month(1), [("A", 2), ("B", -1), ("D", -1)]));
+
+        let names = |players: BTreeSet<String>| players.into_iter().collect::<Vec<_>>();
+        assert_eq!(names(data.active_players(None)), ["A", "B", "C", "D"]);
+        let june = chrono::NaiveDate::from_ymd_opt(2024, 6, 1);
+        assert!(data.active_players(june).is_empty());
+
+        data.play(play(month(7), [("A", 2), ("B", -1), ("C", -1)]));
+        assert_eq!(names(data.active_players(june)), ["A", "B", "C"]);
+
+        data.start_season(
+            "Autumn".to_owned(),
+            chrono::NaiveDate::from_ymd_opt(2024, 9, 1),
+        );
+        data.play(play(month(10), [("A", 2), ("C", -1), ("D", -1)]));
+        // The season takes precedence over the date
+        assert_eq!(names(data.active_players(june)), ["A", "C", "D"]);
+
+        // B is hidden, but keeps their rating
+        let before = data.evaluate_up_to(data.history.len() - 2).ratings["B"];
+        assert_eq!(data.evaluate().ratings["B"], before);
+        assert_ne!(before, 0.0);
+    }
+
+    #[test]
+    fn fractional_games() {
+        let mut data = Data::default();
+        data.add_player("A".to_owned(), 1.0);
+        data.add_player("B".to_owned(), 0.0);
+        data.add_player("C".to_owned(), -1.0);
+
+        let mut play = Play::now(
+            1,
+            ["A", "B", "C"].map(|player| Outcome {
+                player: player.to_owned(),
+                score: 0,
+            }),
+        );
+        let rating_after = |games: f64| {
+            let mut play = play.clone();
+            play.set_games(games).unwrap();
+            data.preview(&Change::Play(play)).ratings["A"]
+        };
+
+        let (two, half, three) = (rating_after(2.0), rating_after(2.5), rating_after(3.0));
+        assert!(two > half && half > three, "{two} {half} {three}");
+
+        play.set_games(2.5).unwrap();
+        assert_eq!(play.game_count, 3);
+        assert_eq!(play.fractional_games, Some(2.5));
+        assert_eq!(play.games(), 2.5);
+        // Started games count towards the games played
+        assert_eq!(
+            data.preview(&Change::Play(play.clone())).games_played["A"],
+            3
+        );
+        play.set_games(2.0).unwrap();
+        assert_eq!((play.game_count, play.fractional_games), (2, None));
+
+        for invalid in [0.0, -1.5, f64::NAN, f64::INFINITY] {
+            assert!(play.set_games(invalid).is_err());
+        }
+
+        // Whole game counts give the same results as before
+        assert_eq!(
+            rating_change_fractional([0.1; 3], 2.0, [1.0, 0.0, -1.0], [3, -1, -2]),
+            rating_change(0.1, 2, [1.0, 0.0, -1.0], [3, -1, -2])
+        );
+    }
+
+    #[test]
+    fn rollback_to() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+
+        let win = |day| {
+            let date = chrono::NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+            Change::Play(play(date, [("A", 2), ("B", -1), ("C", -1)]))
+        };
+        data.history.push(win(1));
+        data.history.push(win(2));
+        data.history.push(win(2));
+        data.add_player("D".to_owned(), 0.0);
+        data.history.push(win(3));
+        data.add_player("E".to_owned(), 0.0);
+        data.history.push(win(4));
+
+        let mut rolled = data.clone();
+        assert_eq!(
+            rolled.rollback_to(chrono::NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()),
+            3
+        );
+        assert_eq!(rolled.history, data.history[..7]);
+        assert_eq!(rolled.evaluate(), data.evaluate_up_to(7));
+
+        let mut rolled = data.clone();
+        let before = chrono::NaiveDate::from_ymd_opt(2024, 4, 30).unwrap();
+        assert_eq!(rolled.rollback_to(before), data.history.len() - 3);
+
+        let mut rolled = data.clone();
+        let after = chrono::NaiveDate::from_ymd_opt(2024, 5, 4).unwrap();
+        assert_eq!(rolled.rollback_to(after), 0);
+        assert_eq!(rolled, data);
+    }
+
+    #[test]
+    fn histogram() {
+        let mut data = Data::default();
+        for (player, display) in [("A", 100.0), ("B", 105.0), ("C", 110.0)] {
+            data.add_player_display(player.to_owned(), display).unwrap();
+        }
+
+        let eval = data.evaluate();
+        assert_eq!(
+            eval.histogram(10.0, &data.config),
+            BTreeMap::from([(10, 2), (11, 1)])
+        );
+        assert_eq!(
+            eval.histogram(3.0, &data.config),
+            BTreeMap::from([(33, 1), (35, 1), (36, 1)])
+        );
+    }
+
+    #[test]
+    fn intransitive_cycles() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C", "D", "E"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+
+        // A beats B, B beats C and C beats A
+        data.play(play(Default::default(), [("A", 2), ("B", 0), ("D", -2)]));
+        data.play(play(Default::default(), [("B", 2), ("C", 0), ("D", -2)]));
+        data.play(play(Default::default(), [("C", 2), ("A", 0), ("E", -2)]));
+
+        let balances = data.pairwise_balances();
+        assert_eq!(balances[&("A".to_owned(), "B".to_owned())], 2);
+        assert_eq!(balances[&("A".to_owned(), "C".to_owned())], -2);
+
+        assert_eq!(data.intransitive_cycles(3), [["A", "B", "C"]]);
+        assert_eq!(data.intransitive_cycles(2), Vec::<Vec<String>>::new());
+
+        // Once A beats C too, there is no cycle left
+        data.play(play(Default::default(), [("A", 4), ("C", 0), ("E", -4)]));
+        assert!(data.intransitive_cycles(5).is_empty());
+    }
+
+    #[test]
+    fn date_format() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let mut config = Config::default();
+        assert_eq!(config.format_date(date), "2024-06-01");
+
+        config.date_format = "%d.%m.%Y".to_owned();
+        assert!(Config::check_date_format(&config.date_format).is_ok());
+        assert_eq!(config.format_date(date), "01.06.2024");
+
+        assert!(Config::check_date_format("%Q").is_err());
+        assert!(Config::check_date_format("%H:%M").is_err());
+
+        config.date_format = "%H".to_owned();
+        assert_eq!(config.format_date(date), "2024-06-01");
+        let mut data = Data {
+            config,
+            ..Default::default()
+        };
+        assert!(parse_data(&toml::to_string(&data).unwrap(), Format::Toml).is_err());
+        data.config.date_format = "%d.%m.%Y".to_owned();
+        assert!(parse_data(&toml::to_string(&data).unwrap(), Format::Toml).is_ok());
+
+        let old: Config =
+            toml::from_str("spread = 50.0\nbase_rating = 100.0\nstarting_alpha = 0.02").unwrap();
+        assert_eq!(old.date_format, "%Y-%m-%d");
+    }
+
+    #[test]
+    fn changes_between() {
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.play(play(Default::default(), [("A", 2), ("B", -1), ("C", -1)]));
+        data.adjust_α(0.1);
+        data.play(play(Default::default(), [("A", 0), ("B", 1), ("C", -1)]));
+        data.set_rating("B".to_owned(), 0.5);
+        data.play(play(Default::default(), [("A", -2), ("B", 1), ("C", 1)]));
+
+        for (start, end) in [(0, 0), (0, 8), (2, 5), (4, 7), (8, 8)] {
+            let changes = data.changes_between(start, end);
+            assert_eq!(changes.len(), end - start);
+            assert!(changes
+                .iter()
+                .zip(&data.history[start..end])
+                .all(|(a, b)| std::ptr::eq(a, b)));
+        }
+
+        let moves = data.rating_moves_between("A", 2, 8);
+        assert_eq!(
+            moves
+                .iter()
+                .map(|event| event.history_index)
+                .collect::<Vec<_>>(),
+            vec![3, 5, 7]
+        );
+        assert!(data.rating_moves_between("A", 4, 5).is_empty());
+        assert!(moves.iter().all(|event| event.delta() != Some(0.0)));
+
+        let moves = data.rating_moves_between("B", 0, 8);
+        assert_eq!(
+            moves
+                .iter()
+                .map(|event| event.history_index)
+                .collect::<Vec<_>>(),
+            vec![1, 3, 5, 6, 7]
+        );
+        assert_eq!(moves[0].before, None);
+    }
+
+    #[test]
+    fn sessions() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+
+        let mut data = Data::default();
+        for player in ["A", "B", "C"] {
+            data.add_player(player.to_owned(), 0.0);
+        }
+        data.play(play(date, [("A", 2), ("B", -1), ("C", -1)]));
+        data.play(play(date, [("A", -1), ("B", 2), ("C", -1)]));
+        assert_eq!(data.rating_timeline().len(), 1);
+
+        data.history.truncate(3);
+        data.play(Play {
+            session: Some(1),
+            ..play(date, [("A", 2), ("B", -1), ("C", -1)])
+        });
+        data.play(Play {
+            session: Some(2),
+            ..play(date, [("A", -1), ("B", 2), ("C", -1)])
+        });
+        let table = data.ratings_table(&TableOptions::default());
+        let rows: Vec<&str> = table.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].starts_with("2024-06-01 #1\t"));
+        assert!(rows[2].starts_with("2024-06-01 #2\t"));
+        assert_ne!(rows[1], rows[2]);
+        assert_eq!(
+            data.ratings_table_append(&TableOptions::default(), &table)
+                .unwrap(),
+            ""
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_evaluation() {
+        use rand::SeedableRng;
+
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let end = chrono::NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let mut data = Data::generate(
+            30,
+            2000,
+            start..=end,
+            &mut rand::rngs::StdRng::seed_from_u64(7),
+        )
+        .unwrap();
+        data.adjust_α(0.05);
+        data.set_rating("Player 1".to_owned(), 0.5);
+
+        assert_eq!(data.evaluate_parallel(), data.evaluate());
+
+        data.config.max_rating_change_per_play = Some(0.5);
+        data.config.rating_floor = Some(90.0);
+        data.config.clamp_internal_ratings = true;
+        data.config.auto_center = true;
+        assert_eq!(data.evaluate_parallel(), data.evaluate());
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name_for("Németh Márton"), "Németh Márton");
+        assert_eq!(file_name_for("A/B\\C:D"), "A_B_C_D");
+        assert_eq!(file_name_for("../x"), "_x");
+        assert_eq!(file_name_for(".."), "_");
+    }
+
+    #[test]
+    fn fit_display() {
+        let mut data = Data::default();
+        for (player, rating) in [("A", 0.7), ("B", -0.1), ("C", 0.2), ("D", -0.5)] {
+            data.add_player(player.to_owned(), rating);
+        }
+
+        let (spread, base_rating) = data.evaluate().fit_display(1500.0, 200.0).unwrap();
+        data.config.spread = spread;
+        data.config.base_rating = base_rating;
+
+        let displayed: Vec<f64> = data
+            .evaluate()
+            .ratings
+            .values()
+            .map(|rating| data.config.rating_to_display(*rating))
+            .collect();
+        let mean = displayed.iter().sum::<f64>() / 4.0;
+        let variance = displayed.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / 4.0;
+        assert!((mean - 1500.0).abs() < 1e-9);
+        assert!((variance.sqrt() - 200.0).abs() < 1e-9);
+
+        assert_eq!(
+            data.evaluate().fit_display(1500.0, 0.0).unwrap_err(),
+            "the target standard deviation is 0, it must be positive"
+        );
+        assert_eq!(
+            data.evaluate().fit_display(f64::NAN, 200.0).unwrap_err(),
+            "the target mean is NaN, it must be a finite number"
+        );
+        data.history.truncate(1);
+        assert!(data.evaluate().fit_display(1500.0, 200.0).is_err());
+    }
+
+    #[test]
+    fn broken_spread() {
+        let mut data = Data::default();
+        assert!(data.add_player_display("A".to_owned(), 120.0).is_ok());
+
+        for spread in [0.0, -50.0, f64::NAN] {
+            data.config.spread = spread;
+            assert!(data.config.check_spread().is_err());
+            assert!(data.config.try_rating_from_display(120.0).is_err());
+            assert!(data.add_player_display("B".to_owned(), 120.0).is_err());
+            assert!(data.set_rating_display("A".to_owned(), 90.0).is_err());
+            assert!(data.import_elo("C,1600", 1500.0, 200.0).is_err());
+        }
+        assert_eq!(data.history.len(), 1);
+        assert!(data.evaluate().ratings["A"].is_finite());
+
+        data.config.spread = 50.0;
+        assert!(data.config.try_rating_from_display(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn participation() {
+        use rand::SeedableRng;
+
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut data = Data::generate(
+            6,
+            40,
+            start..=start + chrono::Days::new(30),
+            &mut rand::rngs::StdRng::seed_from_u64(3),
+        )
+        .unwrap();
+        data.set_rating("Player 2".to_owned(), 0.5);
+        data.adjust_α(0.05);
+
+        let participation = data.participation();
+        assert_eq!(participation.len(), 6);
+        for (player, counts) in &participation {
+            assert_eq!(
+                counts.values().sum::<usize>(),
+                data.references(player).len()
+            );
+            assert_eq!(counts["add_player"], 1);
+        }
+        let plays: usize = participation
+            .values()
+            .filter_map(|counts| counts.get("play"))
+            .sum();
+        assert_eq!(plays, 3 * 40);
+        assert_eq!(participation["Player 2"]["set_rating"], 1);
+        assert!(!participation["Player 1"].contains_key("adjust_alpha"));
+    }
+
+    #[test]
+    fn confirm_words() {
+        let mut config = Config::default();
+        assert_eq!(config.confirmation_hint(), "(y/N)");
+        for answer in ["y", "Y", "yes", "YES", " Yes\n"] {
+            assert!(config.is_confirmation(answer));
+        }
+        for answer in ["", "n", "no", "igen", "yess"] {
+            assert!(!config.is_confirmation(answer));
+        }
+
+        config.confirm_words = vec!["igen".to_owned(), "I".to_owned()];
+        for answer in ["igen", "IGEN", "i", "I\n"] {
+            assert!(config.is_confirmation(answer));
+        }
+        for answer in ["y", "yes", "nem"] {
+            assert!(!config.is_confirmation(answer));
+        }
+        assert_eq!(config.confirmation_hint(), "(igen/N)");
+
+        config.confirm_words.clear();
+        assert_eq!(config.confirmation_hint(), "(N)");
+    }
+
+    #[test]
+    fn invalid_max_rating_change() {
+        assert!(Config::check_max_rating_change(5.0).is_ok());
+        for max in [0.0, -5.0, f64::NAN, f64::INFINITY] {
+            assert!(Config::check_max_rating_change(max).is_err());
+        }
+
+        let mut data = Data::default();
+        data.config.max_rating_change_per_play = Some(-5.0);
+        let toml = toml::to_string(&data).unwrap();
+        assert!(parse_data(&toml, Format::Toml).is_err());
     }
 }
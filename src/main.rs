@@ -1,12 +1,20 @@
 #![allow(confusable_idents, mixed_script_confusables)]
 
 use std::{
-    io,
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Write},
     path::{Path, PathBuf},
     process,
+    sync::OnceLock,
+    time::Duration,
 };
 
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand};
+
+const CONSERVATION_EPS: f64 = 1e-9;
+#[cfg(feature = "progress")]
+const PROGRESS_THRESHOLD: usize = 10_000;
 
 /// Ulti rating calculator
 ///
@@ -14,20 +22,68 @@ use clap::{Parser, Subcommand};
 ///
 /// Names are case sensitive. Using full names is recommended for players. You don't have to write out the full name.
 ///
-/// 1. Given a pattern, if an exact match exists, that will be used.
+/// 1. Given a pattern, if an exact match exists, that will be used. With --prefer-prefix, other matches are still considered, so an exact match can be ambiguous.
 /// 2. Otherwist a pattern matches the name iff there exists such a subsequence of the words of the name, the words of the pattern are prefixes of the corresponding words of the subsequence.
 ///
 /// Example: "Márton" will match "Németh Márton" but not "Németh Marcell". "Németh M" will match both "Németh Márton" and "Németh Marcell" and therefore will give an error. "Dani" will match "Dániel".
 #[derive(Debug, Parser)]
 #[clap(version)]
 struct Cli {
-    /// File containing the data
+    /// File containing the data, stored as JSON if the extension is .json, TOML otherwise
     #[arg(default_value = "ultira.toml", short, long)]
     file: PathBuf,
+    /// An exact name match is used even if the pattern matches other names too (default)
+    #[arg(long, action, global = true)]
+    strict_exact: bool,
+    /// An exact name match is ambiguous if the pattern matches other names too, e.g. "Ann" if "Anna" also exists
+    #[arg(long, action, global = true, conflicts_with = "strict_exact")]
+    prefer_prefix: bool,
+    /// Never write the file, commands which would modify it fail instead
+    #[arg(long, action, global = true)]
+    read_only: bool,
+    /// If a name matches multiple players, ask which one was meant instead of aborting
+    #[arg(short, long, action, global = true)]
+    interactive: bool,
+    /// If another process is modifying the file, wait for it to finish instead of failing. The lock is kept in a .lock file next to it, which is left in place
+    #[arg(long, action, global = true)]
+    wait_for_lock: bool,
+    /// Read the file back after writing it and fail if it doesn't evaluate to the same ratings
+    #[arg(long, action, global = true)]
+    verify_writes: bool,
+    /// Answer yes to every confirmation, e.g. for scripts
+    #[arg(short, long, action, global = true)]
+    yes: bool,
+    /// Apply consecutive plays of different players concurrently, for very large files
+    #[cfg(feature = "parallel")]
+    #[arg(long, action, global = true)]
+    parallel: bool,
     #[command(subcommand)]
     command: Command,
 }
 
+/// Global options affecting the shared helpers of commands
+#[derive(Debug)]
+struct Options {
+    match_mode: ultira::MatchMode,
+    /// Writing the data file is an error
+    read_only: bool,
+    /// Ask which player was meant if a name is ambiguous
+    interactive: bool,
+    /// Re-read and compare the data file after writing it
+    verify_writes: bool,
+    /// Confirmations pass without asking
+    yes: bool,
+    /// Evaluate with [`ultira::Data::evaluate_parallel`]
+    #[cfg(feature = "parallel")]
+    parallel: bool,
+}
+
+static OPTIONS: OnceLock<Options> = OnceLock::new();
+
+fn options() -> &'static Options {
+    OPTIONS.get().expect("options are set at startup")
+}
+
 #[derive(Debug, Subcommand)]
 enum Command {
     /// Evaluate rating changes after a play.
@@ -39,14 +95,46 @@ enum Command {
     /// Each play has a date associated with it. If not specified, the system's date will be used in the proleptic Gregorian calendar. Monotonity is not guaranteed.
     #[command(visible_alias = "p")]
     Play(Play),
+    /// Record a play giving only two scores, the third one is inferred so that they sum to 0
+    Play2(Play2),
+    /// Record a play where every player scored 0.
+    ///
+    /// The games still count towards the number of games played. Ratings stay the same only if the three players had equal ratings, otherwise they move towards their average, as with any other play.
+    Draw(Draw),
     /// Create or clear the file
     New(New),
+    /// Overwrite the file with random players and plays, e.g. for demos and benchmarks
+    Generate(Generate),
     /// Add a new player; if the player already exists, their rating will be overriden.
     #[command(visible_alias = "add")]
     AddPlayer(AddPlayer),
+    /// Print the position of a player in the ratings and the number of players ranked, tied players share their position. Players need config ranking-min-games games to be ranked
+    Rank(Rank),
+    /// Print each player's probability of finishing a hypothetical play with the highest score.
+    ///
+    /// Ratings only predict scores, so this assumes a logistic model: a rating difference of one spread makes winning e times as likely.
+    Odds(Odds),
+    /// Print the results of two players in the plays they were both part of
+    ///
+    /// The balance is how much more the first player scored than the second one.
+    HeadToHead(HeadToHead),
+    /// Print cycles of players in which each one outscored the next one in their plays together, e.g. A beats B beats C beats A
+    Intransitivity(Intransitivity),
+    /// Print a bar chart of the number of players per display rating range
+    Distribution(Distribution),
+    /// Print the spread and base rating which would display the current ratings with the given mean and standard deviation.
+    ///
+    /// This only changes how ratings are displayed, not the ratings themselves. The rating floor and ceiling are ignored.
+    FitDisplay(FitDisplay),
+    /// Print the three players with the closest ratings, for the fairest next play
+    Balance(Balance),
+    /// Reset the rating of an existing player, without affecting the history of others
+    ResetPlayer(ResetPlayer),
     /// Print the ratings of the players
     #[command(visible_alias = "r")]
-    Ratings,
+    Ratings(Ratings),
+    /// Start a new season, seasons don't affect ratings but can be listed separately with ratings --season
+    Season(Season),
     /// Get and set config.
     ///
     /// Not passing any parameters to config will show to current value.
@@ -55,18 +143,135 @@ enum Command {
     ///
     /// These are
     /// - play
+    /// - draw
     /// - add-player
+    /// - reset-player
     /// - adjust realloc
     Undo(Undo),
+    /// Set the date of the change at the given index of the history.
+    ///
+    /// Dates don't affect the order of changes, so ratings don't change. Changes without dates, such as adding players, can't be modified. This can't be undone with undo.
+    SetDate(SetDate),
     /// Renames a player to a new name, also allows merging players
     #[command(visible_alias = "rename")]
     RenamePlayer(RenamePlayer),
+    /// Replace old history with the ratings it results in.
+    ///
+    /// Every change before the first one dated on or after the given date is replaced by adding the players with their ratings and games played at that point. α adjustments and season boundaries are kept, but seasons started before the date lose the games before it. Later history is kept. This cannot be undone.
+    Compact(Compact),
+    /// Remove every change from the first one dated after the given date on, as if they never happened.
+    ///
+    /// Unlike compact, ratings return to what they were at that date. Undated changes before the first removed one are kept. This cannot be undone.
+    Rollback(Rollback),
+    /// Write a new file with the same config, adding every player at their current rating, e.g. to archive standings or start a fresh season.
+    ///
+    /// The new file has no game history, so ratings hides its players until they reach ranking-min-games again. This file isn't modified.
+    Snapshot(Snapshot),
+    /// Remove changes identical to the one before them, e.g. plays entered twice by accident. This cannot be undone.
+    Dedup(Dedup),
+    /// Restore the file from its most recent readable backup if it can't be read.
+    ///
+    /// Backups are files next to it named like the file, but ending in .bak, e.g. ultira.toml.bak. The unreadable file is kept with a .corrupt extension.
+    Repair,
+    /// Check the file for problems, exits with a non-zero code if any are found
+    Check(Check),
+    /// Compare the ratings with the ratings of another file, in display units
+    ///
+    /// Differences are this file's ratings minus the other file's ones, using this file's spread.
+    DiffFile(DiffFile),
+    /// Explain a player's rating by listing every change which affected it
+    Why(Why),
+    /// Print every change of the history with its index, date, and the score multiplier in force before it
+    History(History),
+    /// Print the index and kind of every change mentioning a player
+    References(References),
+    /// Print a sparkline of every player's rating over the changes affecting it
+    Trends,
+    /// Print the players whose display ratings rose and fell the most since a date
+    ///
+    /// Players added since the date are left out.
+    Movers(Movers),
+    /// Print every date with activity and the number of changes on it
+    Dates,
+    /// Print how far the average rating is from the base rating after every date with activity, ignoring auto-center.
+    ///
+    /// Plays keep the average, unless clamped or with per-player score multipliers, so drift not explained by added players or reset ratings points to a bug.
+    Inflation,
+    /// Export the full history as pretty-printed JSON
+    ExportHistory(ExportHistory),
+    /// Export the display ratings after each date with activity as tab separated values
+    ExportRatings(ExportRatings),
+    /// Export a Graphviz DOT graph of who played whom, weighted by the number of games
+    ExportGraph(ExportGraph),
+    /// Export the display ratings of each player to a separate tab separated file, e.g. for plotting players independently
+    ExportTimelines(ExportTimelines),
+    /// Import a history exported as JSON, either replacing or appending to the current one.
+    ///
+    /// The resulting history is validated before anything is written.
+    ImportHistory(ImportHistory),
+    /// Add the players of a CSV file with name,elo rows, converting their ELO to display ratings.
+    ///
+    /// An ELO of the center becomes the base rating, and k ELO points become one spread. Existing players' ratings are overriden. Nothing is added if any row is invalid.
+    ImportElo(ImportElo),
+    /// Append the plays of a score log with one "<date> <game count> <name> <score> <name> <score> <name> <score>" line per play.
+    ///
+    /// Names are matched like everywhere else, empty lines and lines starting with # are skipped. Nothing is added if any line is invalid.
+    ImportLog(ImportLog),
+}
+
+impl Command {
+    /// Whether the command may modify the data file
+    fn is_mutating(&self) -> bool {
+        match self {
+            Command::Play(_)
+            | Command::Play2(_)
+            | Command::Draw(_)
+            | Command::New(_)
+            | Command::Generate(_)
+            | Command::AddPlayer(_)
+            | Command::ResetPlayer(_)
+            | Command::Season(_)
+            | Command::Undo(_)
+            | Command::SetDate(_)
+            | Command::RenamePlayer(_)
+            | Command::Compact(_)
+            | Command::Rollback(_)
+            | Command::Dedup(_)
+            | Command::Repair
+            | Command::ImportHistory(_)
+            | Command::ImportElo(_)
+            | Command::ImportLog(_) => true,
+            Command::Config(config) => config.param.is_set(),
+            Command::FitDisplay(fit) => fit.apply,
+            Command::Ratings(_)
+            | Command::Rank(_)
+            | Command::Odds(_)
+            | Command::Balance(_)
+            | Command::Distribution(_)
+            | Command::Intransitivity(_)
+            | Command::HeadToHead(_)
+            | Command::Check(_)
+            | Command::DiffFile(_)
+            | Command::Why(_)
+            | Command::History(_)
+            | Command::References(_)
+            | Command::Trends
+            | Command::Snapshot(_)
+            | Command::Movers(_)
+            | Command::Dates
+            | Command::Inflation
+            | Command::ExportHistory(_)
+            | Command::ExportRatings(_)
+            | Command::ExportGraph(_)
+            | Command::ExportTimelines(_) => false,
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
 struct Play {
-    /// Number of games
-    game_count: usize,
+    /// Number of games, may be fractional for interrupted sessions, e.g. 2.5
+    game_count: f64,
     /// Name of player 1
     player_1: String,
     /// Total score of player 1
@@ -85,23 +290,252 @@ struct Play {
     /// Specify the date of the play, does not affect the order of the plays. Format: YYYY-MM-DD
     #[arg(short = 'd', long)]
     date: Option<chrono::NaiveDate>,
+    /// Don't print the rating changes
+    #[arg(short = 'q', long, action)]
+    quiet: bool,
+    /// Add players whose names don't match anyone, as add-player without a rating would, instead of aborting
+    #[arg(long, action)]
+    auto_add: bool,
+    /// Compare the scores with the ones expected from the ratings before the play
+    #[arg(long, action)]
+    surprise: bool,
+    /// Free text attached to the play, e.g. "tournament final"
+    #[arg(long)]
+    note: Option<String>,
+    /// Number of the session on the date, to tell apart separate sessions on the same date in exports
+    #[arg(long)]
+    session: Option<u32>,
+}
+
+#[derive(Debug, Parser)]
+struct Play2 {
+    /// Number of games
+    game_count: usize,
+    /// Name of player 1
+    player_1: String,
+    /// Total score of player 1
+    #[arg(allow_hyphen_values = true)]
+    score_1: i64,
+    /// Name of player 2
+    player_2: String,
+    /// Total score of player 2
+    #[arg(allow_hyphen_values = true)]
+    score_2: i64,
+    /// Name of player 3, whose score is inferred
+    player_3: String,
+    /// Specify the date of the play, does not affect the order of the plays. Format: YYYY-MM-DD
+    #[arg(short = 'd', long)]
+    date: Option<chrono::NaiveDate>,
+    /// Don't print the rating changes
+    #[arg(short = 'q', long, action)]
+    quiet: bool,
+    /// Add players whose names don't match anyone, as add-player without a rating would, instead of aborting
+    #[arg(long, action)]
+    auto_add: bool,
+}
+
+#[derive(Debug, Parser)]
+struct Draw {
+    /// Number of games
+    game_count: usize,
+    /// Name of player 1
+    player_1: String,
+    /// Name of player 2
+    player_2: String,
+    /// Name of player 3
+    player_3: String,
+    /// Specify the date of the play, does not affect the order of the plays. Format: YYYY-MM-DD
+    #[arg(short = 'd', long)]
+    date: Option<chrono::NaiveDate>,
+    /// Don't print the rating changes
+    #[arg(short = 'q', long, action)]
+    quiet: bool,
+    /// Add players whose names don't match anyone, as add-player without a rating would, instead of aborting
+    #[arg(long, action)]
+    auto_add: bool,
 }
 
 #[derive(Debug, Parser)]
 struct New {
     #[arg(short = 'n', long, action)]
     no_confirm: bool,
+    /// Spread of the new file, see config spread
+    #[arg(long)]
+    spread: Option<f64>,
+    /// Base rating of the new file, see config base-rating
+    #[arg(long)]
+    base: Option<f64>,
+    /// Internal starting α of the new file, the score multiplier is α * spread
+    #[arg(long)]
+    alpha: Option<f64>,
+}
+
+#[derive(Debug, Parser)]
+struct Generate {
+    /// Number of players
+    #[arg(long, default_value_t = 8)]
+    players: usize,
+    /// Number of plays
+    #[arg(long, default_value_t = 100)]
+    plays: usize,
+    /// Seed of the random number generator, the same seed generates the same data
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Date of the earliest play, defaults to a year before the latest one. Format: YYYY-MM-DD
+    #[arg(long)]
+    from: Option<chrono::NaiveDate>,
+    /// Date of the latest play, defaults to today. Format: YYYY-MM-DD
+    #[arg(long)]
+    to: Option<chrono::NaiveDate>,
+    #[arg(short = 'n', long, action)]
+    no_confirm: bool,
 }
 
 #[derive(Debug, Parser)]
 struct AddPlayer {
     /// The name of the new player
     player: String,
-    /// The rating of the new player
+    /// The rating of the new player, defaults according to the new player policy
+    #[arg(allow_hyphen_values = true)]
+    rating: Option<f64>,
+}
+
+#[derive(Debug, Parser)]
+struct Rank {
+    /// The name of the player
+    player: String,
+}
+
+#[derive(Debug, Parser)]
+struct Odds {
+    /// Name of player 1
+    player_1: String,
+    /// Name of player 2
+    player_2: String,
+    /// Name of player 3
+    player_3: String,
+}
+
+#[derive(Debug, Parser)]
+struct HeadToHead {
+    /// Name of the first player
+    player_a: String,
+    /// Name of the second player
+    player_b: String,
+    /// "year" to split up the results by the calendar year of the plays
+    #[arg(long, default_value = "none")]
+    group_by: ultira::GroupBy,
+}
+
+#[derive(Debug, Parser)]
+struct Intransitivity {
+    /// Longest cycles to look for, the number of cycles grows quickly with it
+    #[arg(long, default_value_t = 3)]
+    max_length: usize,
+}
+
+#[derive(Debug, Parser)]
+struct Distribution {
+    /// Width of the display rating ranges
+    #[arg(long, default_value_t = 10.0)]
+    bin_width: f64,
+}
+
+#[derive(Debug, Parser)]
+struct FitDisplay {
+    /// Mean of the displayed ratings
+    #[arg(long, allow_hyphen_values = true)]
+    target_mean: f64,
+    /// Population standard deviation of the displayed ratings
+    #[arg(long)]
+    target_std: f64,
+    /// Also set the spread and base-rating configs
+    #[arg(long, action)]
+    apply: bool,
+}
+
+#[derive(Debug, Parser)]
+struct Balance {
+    /// Names of the players to choose from, defaults to every player
+    candidates: Vec<String>,
+}
+
+#[derive(Debug, Parser)]
+struct Movers {
+    /// Compare with the ratings before this date. Format: YYYY-MM-DD
+    #[arg(long)]
+    since: chrono::NaiveDate,
+    /// Number of gainers and losers to print
+    #[arg(long, default_value_t = 3)]
+    top: usize,
+}
+
+#[derive(Debug, Parser)]
+struct ResetPlayer {
+    /// The name of the player
+    player: String,
+    /// The new rating of the player, defaults to the base rating
     #[arg(allow_hyphen_values = true)]
     rating: Option<f64>,
 }
 
+#[derive(Debug, Parser)]
+struct Ratings {
+    /// Hide players with fewer games, defaults to the ranking-min-games config
+    #[arg(long)]
+    min_games: Option<usize>,
+    /// Use a comma as the decimal separator, defaults to the display.decimal_comma config
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    decimal_comma: Option<bool>,
+    /// Number of decimals, defaults to the display.precision config
+    #[arg(long)]
+    precision: Option<usize>,
+    /// Order by "rating" or "name", defaults to the display.sort config
+    #[arg(long)]
+    sort: Option<ultira::RatingSort>,
+    /// Only count games since the start of the last season with this name, with every player starting it at the same rating, hiding players who haven't played in it
+    #[arg(long)]
+    season: Option<String>,
+    /// Only show players who played or were changed since the start of the latest season, or within --inactive days if there are no seasons. Ratings are unaffected
+    #[arg(long, action)]
+    active: bool,
+    /// Without seasons, --active shows players who played in this many days instead
+    #[arg(long, requires = "active")]
+    inactive: Option<u64>,
+    /// Evaluate as if the score multiplier had always been this display value, without writing anything
+    ///
+    /// This replaces the starting score multiplier and every later adjustment of it.
+    #[arg(long)]
+    alpha: Option<f64>,
+    /// Only evaluate the first this many changes of the history, see the indices printed by history
+    #[arg(long, conflicts_with = "season")]
+    up_to: Option<usize>,
+    /// Print the signed difference from this player's rating instead of the ratings
+    #[arg(long)]
+    relative_to: Option<String>,
+    /// Print the internal ratings, independent of spread and base-rating, with 6 decimals unless --precision is given
+    #[arg(long, action)]
+    internal: bool,
+    /// Also print how many changes of each kind each player was part of, e.g. plays and set ratings, over the whole history
+    #[arg(long, action, conflicts_with_all = ["season", "up_to"])]
+    breakdown: bool,
+    /// Keep running and print the ratings again whenever the file changes, e.g. for a projector during a tournament
+    #[arg(long, action)]
+    watch: bool,
+    /// Print how long the evaluation took to stderr
+    #[arg(long, action, hide = true)]
+    timings: bool,
+}
+
+#[derive(Debug, Parser)]
+struct Season {
+    /// Name of the season
+    name: String,
+    /// Specify the date the season starts, defaults to today. Format: YYYY-MM-DD
+    #[arg(short = 'd', long)]
+    date: Option<chrono::NaiveDate>,
+}
+
 #[derive(Debug, Parser)]
 #[command(subcommand_help_heading = "Params", subcommand_value_name = "PARAM")]
 struct Config {
@@ -120,12 +554,119 @@ enum Param {
     ///
     /// This affects both display and internal ratings. Modifications get commited to history, only affects new plays.
     #[command(visible_alias = "μ")]
-    ScoreMultiplier { new_value: Option<f64> },
+    ScoreMultiplier {
+        new_value: Option<f64>,
+        /// Print the internal α driving the math instead of the display value
+        #[arg(long, action, conflicts_with = "new_value")]
+        internal: bool,
+        /// Why the score multiplier is being changed, kept in the history
+        #[arg(long, requires = "new_value")]
+        reason: Option<String>,
+    },
     /// Adjusting the base rating will increase ratings by the difference between the new and old one.
     ///
     /// This affects only display ratings, not internal ones. Modifications do not get commited to history.
     #[command(visible_alias = "δ")]
     BaseRating { new_value: Option<f64> },
+    /// Rating of players added without an explicit rating: "base" uses the base rating, "average" the current average rating.
+    ///
+    /// Modifications do not get commited to history, only affects new players.
+    NewPlayerPolicy {
+        new_value: Option<ultira::NewPlayerPolicy>,
+    },
+    /// Multiplies the score multiplier for a single player, making their rating react faster or slower.
+    ///
+    /// This affects internal ratings. Modifications do not get commited to history, they affect the whole history.
+    PlayerAlpha {
+        player: String,
+        new_value: Option<f64>,
+    },
+    /// Players with fewer games are hidden from the ratings by default.
+    ///
+    /// This only affects display. Modifications do not get commited to history.
+    RankingMinGames { new_value: Option<usize> },
+    /// Keep the average rating at the base rating by shifting every rating.
+    ///
+    /// This only affects the absolute level of ratings, not their differences. Modifications do not get commited to history.
+    AutoCenter { new_value: Option<bool> },
+    /// Plays with more games are rejected as likely typos.
+    ///
+    /// Modifications do not get commited to history.
+    MaxGameCount { new_value: Option<usize> },
+    /// Largest change of a player's display rating a single play can cause, unlimited by default.
+    ///
+    /// Modifications do not get commited to history, they affect the whole history.
+    MaxRatingChangePerPlay {
+        new_value: Option<f64>,
+        /// Remove the limit
+        #[arg(long, action, conflicts_with = "new_value")]
+        unset: bool,
+    },
+    /// Lowest display rating shown, unlimited by default.
+    ///
+    /// This only affects display, unless clamp-internal-ratings is set. Modifications do not get commited to history.
+    RatingFloor {
+        #[arg(allow_hyphen_values = true)]
+        new_value: Option<f64>,
+        /// Remove the limit
+        #[arg(long, action, conflicts_with = "new_value")]
+        unset: bool,
+    },
+    /// Highest display rating shown, unlimited by default.
+    ///
+    /// This only affects display, unless clamp-internal-ratings is set. Modifications do not get commited to history.
+    RatingCeiling {
+        #[arg(allow_hyphen_values = true)]
+        new_value: Option<f64>,
+        /// Remove the limit
+        #[arg(long, action, conflicts_with = "new_value")]
+        unset: bool,
+    },
+    /// Also clamp internal ratings to the floor and ceiling after every change.
+    ///
+    /// This distorts the math, clamped players keep playing from the clamped rating. Modifications do not get commited to history, they affect the whole history.
+    ClampInternalRatings { new_value: Option<bool> },
+    /// Number of decimals internal ratings of added players are stored with, full precision by default.
+    ///
+    /// Shorter numbers keep diffs of the file readable, at the cost of a tiny rounding of those ratings. Existing ratings are rounded on the next write.
+    StorePrecision {
+        new_value: Option<u32>,
+        /// Store full precision
+        #[arg(long, action, conflicts_with = "new_value")]
+        unset: bool,
+    },
+    /// chrono format string of dates in listings and exports, "%Y-%m-%d" by default.
+    ///
+    /// For example "%d.%m.%Y". Dates are still entered as YYYY-MM-DD.
+    DateFormat { new_value: Option<String> },
+    /// Answers accepted by confirmation prompts, "y yes" by default. Case is ignored.
+    ///
+    /// For example "igen i". Giving words replaces the current ones.
+    ConfirmWords { new_value: Vec<String> },
+    /// Print every config field with its type, current and default value
+    Schema,
+}
+
+impl Param {
+    fn is_set(&self) -> bool {
+        match self {
+            Param::Spread { new_value } | Param::BaseRating { new_value } => new_value.is_some(),
+            Param::ScoreMultiplier { new_value, .. } => new_value.is_some(),
+            Param::NewPlayerPolicy { new_value } => new_value.is_some(),
+            Param::PlayerAlpha { new_value, .. } => new_value.is_some(),
+            Param::RankingMinGames { new_value } => new_value.is_some(),
+            Param::AutoCenter { new_value } => new_value.is_some(),
+            Param::MaxGameCount { new_value } => new_value.is_some(),
+            Param::MaxRatingChangePerPlay { new_value, unset }
+            | Param::RatingFloor { new_value, unset }
+            | Param::RatingCeiling { new_value, unset } => new_value.is_some() || *unset,
+            Param::ClampInternalRatings { new_value } => new_value.is_some(),
+            Param::StorePrecision { new_value, unset } => new_value.is_some() || *unset,
+            Param::DateFormat { new_value } => new_value.is_some(),
+            Param::ConfirmWords { new_value } => !new_value.is_empty(),
+            Param::Schema => false,
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -134,6 +675,166 @@ struct RenamePlayer {
     new_name: String,
 }
 
+#[derive(Debug, Parser)]
+struct Rollback {
+    /// Keep history up to and including this date. Format: YYYY-MM-DD
+    #[arg(long)]
+    to: chrono::NaiveDate,
+    #[arg(short = 'n', long, action)]
+    no_confirm: bool,
+}
+
+#[derive(Debug, Parser)]
+struct Snapshot {
+    /// The file to write the snapshot to, its format is chosen by its extension
+    out_file: PathBuf,
+    #[arg(short = 'n', long, action)]
+    no_confirm: bool,
+}
+
+#[derive(Debug, Parser)]
+struct Compact {
+    /// Compact history before this date. Format: YYYY-MM-DD
+    #[arg(long)]
+    before: chrono::NaiveDate,
+    #[arg(short = 'n', long, action)]
+    no_confirm: bool,
+}
+
+#[derive(Debug, Parser)]
+struct Dedup {
+    /// Numbers closer than this are considered equal
+    #[arg(long, default_value_t = 1e-9)]
+    epsilon: f64,
+}
+
+#[derive(Debug, Parser)]
+struct Check {
+    /// Also check that every play keeps the sum of the ratings unchanged
+    #[arg(long, action)]
+    conservation: bool,
+    /// Also check that evaluating twice gives bit-identical ratings
+    #[arg(long, action)]
+    determinism: bool,
+    /// Treat warnings as errors
+    #[arg(long, action)]
+    strict: bool,
+    /// Fix the problems which can be fixed automatically, and explain how to fix the rest
+    #[arg(long, action)]
+    fix: bool,
+    /// "json" to print the problems as an array of objects with kind, history_index and message fields.
+    ///
+    /// Warnings are only included with --strict.
+    #[arg(long, default_value = "text", conflicts_with = "fix")]
+    format: ultira::ReportFormat,
+}
+
+#[derive(Debug, Parser)]
+struct History {
+    /// Print every change as a sentence instead, e.g. for sharing with players
+    #[arg(long, action)]
+    narrative: bool,
+}
+
+#[derive(Debug, Parser)]
+struct DiffFile {
+    /// The file to compare with
+    other: PathBuf,
+}
+
+#[derive(Debug, Parser)]
+struct Why {
+    /// The name of the player
+    player: String,
+}
+
+#[derive(Debug, Parser)]
+struct References {
+    /// The name of the player
+    player: String,
+}
+
+#[derive(Debug, Parser)]
+struct ExportHistory {
+    /// File to write the JSON to
+    file: PathBuf,
+}
+
+#[derive(Debug, Parser)]
+struct ExportRatings {
+    /// File to write the table to
+    file: PathBuf,
+    /// "tsv" for ratings over time, "md" for a Markdown table of the current standings
+    #[arg(long, default_value = "tsv")]
+    format: ultira::TableFormat,
+    /// "wide" has a column per player, "tidy" has a row per date and player with columns datum, player and rating
+    #[arg(long, default_value = "wide")]
+    layout: ultira::Layout,
+    /// Only export these players, in this order. Can be repeated
+    #[arg(long = "player")]
+    players: Vec<String>,
+    /// Only add the rows missing from an earlier export to the end of the file, keeping its columns
+    #[arg(long, action, conflicts_with = "players")]
+    append: bool,
+    /// Separates the cells of a row, a tab by default, e.g. ';'
+    #[arg(long)]
+    delimiter: Option<char>,
+    /// Use a comma as the decimal separator, can't be combined with a comma delimiter
+    #[arg(long, action)]
+    decimal_comma: bool,
+}
+
+#[derive(Debug, Parser)]
+struct ExportGraph {
+    /// File to write the DOT graph to
+    file: PathBuf,
+}
+
+#[derive(Debug, Parser)]
+struct ExportTimelines {
+    /// Directory to write a <player>.tsv file per player to, created if missing
+    dir: PathBuf,
+}
+
+#[derive(Debug, Parser)]
+#[command(group(ArgGroup::new("mode").required(true).args(["replace", "append"])))]
+struct ImportHistory {
+    /// File containing the JSON history
+    file: PathBuf,
+    /// Replace the current history
+    #[arg(long, action)]
+    replace: bool,
+    /// Append to the end of the current history
+    #[arg(long, action)]
+    append: bool,
+}
+
+#[derive(Debug, Parser)]
+struct ImportElo {
+    /// CSV file with name,elo rows
+    file: PathBuf,
+    /// ELO difference corresponding to one spread
+    #[arg(long, default_value_t = 200.0)]
+    k: f64,
+    /// ELO corresponding to the base rating
+    #[arg(long, default_value_t = 1500.0)]
+    center: f64,
+}
+
+#[derive(Debug, Parser)]
+struct ImportLog {
+    /// Text file with one play per line, e.g. "2024-06-01 4 Alice 4 Bob -2 Carol -2"
+    file: PathBuf,
+}
+
+#[derive(Debug, Parser)]
+struct SetDate {
+    /// 0-based index of the change in the history
+    index: usize,
+    /// Format: YYYY-MM-DD
+    date: chrono::NaiveDate,
+}
+
 #[derive(Debug, Parser)]
 struct Undo {
     #[arg(short = 'n', long, action)]
@@ -143,124 +844,1153 @@ struct Undo {
 fn play(path: &Path, play: Play) {
     let mut data = read_data(path);
 
-    let Some(player_1) = try_find_name(&data, &play.player_1) else {
+    let Some(outcomes) = resolve_outcomes(
+        &mut data,
+        [
+            (&play.player_1, play.score_1),
+            (&play.player_2, play.score_2),
+            (&play.player_3, play.score_3),
+        ],
+        play.auto_add,
+    ) else {
+        return;
+    };
+
+    if outcomes.iter().map(|o| o.score).sum::<i64>() != 0 {
+        eprintln!("Points don't sum to 0.");
+        return;
+    }
+
+    let mut new = new_play(1, play.date, outcomes);
+    if let Err(err) = new.set_games(play.game_count) {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+    new.note = play.note;
+    new.session = play.session;
+
+    record_play(path, data, new, play.quiet, play.surprise);
+}
+
+fn play2(path: &Path, play: Play2) {
+    let mut data = read_data(path);
+
+    let Some(score_3) = ultira::inferred_score([play.score_1, play.score_2]) else {
+        eprintln!("The score of player 3 would be out of range.");
         return;
     };
 
-    let Some(player_2) = try_find_name(&data, &play.player_2) else {
+    let Some(outcomes) = resolve_outcomes(
+        &mut data,
+        [
+            (&play.player_1, play.score_1),
+            (&play.player_2, play.score_2),
+            (&play.player_3, score_3),
+        ],
+        play.auto_add,
+    ) else {
         return;
     };
 
-    let Some(player_3) = try_find_name(&data, &play.player_3) else {
+    let new = new_play(play.game_count, play.date, outcomes);
+
+    record_play(path, data, new, play.quiet, false);
+}
+
+fn draw(path: &Path, draw: Draw) {
+    let mut data = read_data(path);
+
+    let Some(outcomes) = resolve_outcomes(
+        &mut data,
+        [
+            (&draw.player_1, 0),
+            (&draw.player_2, 0),
+            (&draw.player_3, 0),
+        ],
+        draw.auto_add,
+    ) else {
         return;
     };
 
-    let outcomes = [
+    let play = new_play(draw.game_count, draw.date, outcomes);
+
+    record_play(path, data, play, draw.quiet, false);
+}
+
+/// With `auto_add`, names matching no one are added to `data` as new players
+fn resolve_outcomes(
+    data: &mut ultira::Data,
+    outcomes: [(&String, i64); 3],
+    auto_add: bool,
+) -> Option<[ultira::Outcome; 3]> {
+    let [(player_1, score_1), (player_2, score_2), (player_3, score_3)] = outcomes;
+
+    let mut resolve = |name: &String| {
+        if auto_add {
+            find_or_add_name(data, name)
+        } else {
+            try_find_name(data, name)
+        }
+    };
+
+    Some([
         ultira::Outcome {
-            player: player_1,
-            score: play.score_1,
+            player: resolve(player_1)?,
+            score: score_1,
         },
         ultira::Outcome {
-            player: player_2,
-            score: play.score_2,
+            player: resolve(player_2)?,
+            score: score_2,
         },
         ultira::Outcome {
-            player: player_3,
-            score: play.score_3,
+            player: resolve(player_3)?,
+            score: score_3,
         },
-    ];
+    ])
+}
 
-    if outcomes.iter().map(|o| o.score).sum::<i64>() != 0 {
-        eprintln!("Points don't sum to 0.");
-        return;
+/// Adds the player at the new player rating if the name doesn't match anyone, ambiguous names still abort
+fn find_or_add_name(data: &mut ultira::Data, name: &str) -> Option<String> {
+    if !evaluate(data)
+        .matching_names_with(name, options().match_mode)
+        .is_empty()
+    {
+        return try_find_name(data, name);
+    }
+
+    if let Err(err) = ultira::check_name(name) {
+        eprintln!("{err}");
+        return None;
     }
 
-    let play = match play.date {
+    data.add_player(name.to_owned(), data.new_player_rating());
+    println!("Added new player {name}");
+
+    Some(name.to_owned())
+}
+
+/// Dated today if `date` isn't given
+fn new_play(
+    game_count: usize,
+    date: Option<chrono::NaiveDate>,
+    outcomes: [ultira::Outcome; 3],
+) -> ultira::Play {
+    match date {
         Some(date) => ultira::Play {
-            game_count: play.game_count,
+            game_count,
             date,
             outcomes,
+            note: None,
+            fractional_games: None,
+            session: None,
         },
-        None => ultira::Play::now(play.game_count, outcomes),
-    };
+        None => ultira::Play::now(game_count, outcomes),
+    }
+}
 
-    let eval_before = data.evaluate();
+fn record_play(
+    path: &Path,
+    mut data: ultira::Data,
+    play: ultira::Play,
+    quiet: bool,
+    surprise: bool,
+) {
+    if let Err(err) = data.config.check_play(&play) {
+        eprintln!("{err}");
+        process::exit(1);
+    }
 
-    data.play(play.clone());
+    for outcome in &play.outcomes {
+        if let Err(err) = ultira::check_name(&outcome.player) {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
 
-    let eval_after = data.evaluate();
+    let eval_before = evaluate(&data);
 
-    for ultira::Outcome { player, score: _ } in play.outcomes {
-        println!(
-            "{}: {:.1} -> {:.1}",
-            player,
-            data.config.rating_to_display(eval_before.ratings[&player]),
-            data.config.rating_to_display(eval_after.ratings[&player]),
+    for player in eval_before.clamped_players(&play, &data.config) {
+        eprintln!(
+            "warning: the rating change of {player} was clamped by max-rating-change-per-play"
         );
     }
 
-    ultira::write_data(path, &data).unwrap();
-}
+    data.play(play.clone());
 
-fn new(path: &Path, param: New) {
-    if !param.no_confirm && path.exists() {
-        println!(
-            "Are you sure you want to override {} (y/N)?",
-            path.to_string_lossy()
+    let eval_after = evaluate(&data);
+
+    if !quiet {
+        print_rating_change(
+            &data,
+            &eval_before,
+            &eval_after,
+            play.outcomes.iter().map(|outcome| outcome.player.as_str()),
         );
+    }
 
-        if !confirm() {
-            return;
-        }
+    if surprise {
+        print_surprise(&eval_before, &play);
     }
 
-    ultira::write_data(path, &Default::default()).unwrap();
+    write_data(path, &data);
 }
 
-fn add_player(path: &Path, param: AddPlayer) {
-    let mut data = read_data(path);
+fn print_surprise(eval_before: &ultira::Evaluation, play: &ultira::Play) {
+    let players = [0, 1, 2].map(|i| play.outcomes[i].player.as_str());
+    let predicted = eval_before.predict(players, play.games());
+
+    for (outcome, predicted) in play.outcomes.iter().zip(predicted) {
+        println!(
+            "{}: expected {:+.1}, actual {:+}",
+            outcome.player, predicted, outcome.score
+        );
+    }
+
+    println!(
+        "Surprise: {:.2} points per player per game",
+        eval_before.surprise(&play.outcomes, play.games())
+    );
+}
+
+fn print_rating_change<'a>(
+    data: &ultira::Data,
+    eval_before: &ultira::Evaluation,
+    eval_after: &ultira::Evaluation,
+    players: impl IntoIterator<Item = &'a str>,
+) {
+    for player in players {
+        println!(
+            "{}: {:.1} -> {:.1}",
+            player,
+            data.config
+                .rating_to_listed_display(eval_before.ratings[player]),
+            data.config
+                .rating_to_listed_display(eval_after.ratings[player]),
+        );
+    }
+}
+
+fn new(path: &Path, param: New) {
+    if !param.no_confirm
+        && path.exists()
+        && !confirm(
+            &existing_config(path),
+            &format!(
+                "Are you sure you want to override {}?",
+                path.to_string_lossy()
+            ),
+        )
+    {
+        return;
+    }
+
+    let mut data = ultira::Data::default();
+    if let Some(spread) = param.spread {
+        data.config.spread = spread;
+        if let Err(err) = data.config.check_spread() {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+    if let Some(base) = param.base {
+        data.config.base_rating = base;
+        if let Err(err) = data.config.check_base_rating() {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+    if let Some(alpha) = param.alpha {
+        data.config.starting_alpha = alpha;
+        if let Err(err) = data.config.check_starting_α() {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+
+    write_data(path, &data);
+}
+
+fn generate(path: &Path, param: Generate) {
+    use rand::SeedableRng;
+
+    if !param.no_confirm
+        && path.exists()
+        && !confirm(
+            &existing_config(path),
+            &format!(
+                "Are you sure you want to override {}?",
+                path.to_string_lossy()
+            ),
+        )
+    {
+        return;
+    }
+
+    let to = param
+        .to
+        .unwrap_or_else(|| chrono::Local::now().date_naive());
+    let from = param.from.unwrap_or(to - chrono::Days::new(365));
+    let mut rng = match param.seed {
+        Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+        None => rand::rngs::StdRng::from_entropy(),
+    };
+
+    match ultira::Data::generate(param.players, param.plays, from..=to, &mut rng) {
+        Ok(data) => write_data(path, &data),
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn add_player(path: &Path, param: AddPlayer) {
+    let mut data = read_data(path);
+
+    if let Err(err) = ultira::check_name(&param.player) {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+
+    let added = match param.rating {
+        Some(rating) => data.add_player_display(param.player, rating),
+        None => data
+            .config
+            .check_spread()
+            .map(|()| data.add_player(param.player, data.new_player_rating())),
+    };
+    if let Err(err) = added {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+
+    write_data(path, &data);
+}
+
+fn rank(path: &Path, rank: Rank) {
+    let data = read_data(path);
+
+    let Some(player) = try_find_name(&data, &rank.player) else {
+        process::exit(1);
+    };
+
+    let min_games = data.config.ranking_min_games;
+    let Some((position, count)) = evaluate(&data).rank(&player, min_games) else {
+        println!("{player} isn't ranked before playing {min_games} games");
+        return;
+    };
+
+    println!("{position} / {count}");
+}
+
+fn odds(path: &Path, odds: Odds) {
+    let data = read_data(path);
+
+    let mut players = Vec::new();
+    for pattern in [&odds.player_1, &odds.player_2, &odds.player_3] {
+        let Some(player) = try_find_name(&data, pattern) else {
+            process::exit(1);
+        };
+        players.push(player);
+    }
+
+    let players = [0, 1, 2].map(|i| players[i].as_str());
+    let probabilities = evaluate(&data).win_probabilities(players);
+
+    for (player, probability) in players.iter().zip(probabilities) {
+        println!("{player}: {:.1}%", probability * 100.0);
+    }
+}
+
+fn head_to_head(path: &Path, head_to_head: HeadToHead) {
+    let data = read_data(path);
+
+    let (Some(a), Some(b)) = (
+        try_find_name(&data, &head_to_head.player_a),
+        try_find_name(&data, &head_to_head.player_b),
+    ) else {
+        process::exit(1);
+    };
+
+    let buckets = data.head_to_head(&a, &b, head_to_head.group_by);
+    if buckets.is_empty() {
+        println!("{a} and {b} haven't played together");
+        return;
+    }
+
+    for (bucket, results) in buckets {
+        println!(
+            "{bucket}: {} plays, {} games, {a} {} {b} {} ({:+})",
+            results.plays,
+            results.games,
+            results.scores[0],
+            results.scores[1],
+            results.balance()
+        );
+    }
+}
+
+fn intransitivity(path: &Path, intransitivity: Intransitivity) {
+    let data = read_data(path);
+
+    let cycles = data.intransitive_cycles(intransitivity.max_length);
+    if cycles.is_empty() {
+        println!("No cycles found");
+    }
+
+    for cycle in cycles {
+        println!("{} > {}", cycle.join(" > "), cycle[0]);
+    }
+}
+
+fn fit_display(path: &Path, fit: FitDisplay) {
+    let mut data = read_data(path);
+
+    let (spread, base_rating) = match evaluate(&data).fit_display(fit.target_mean, fit.target_std) {
+        Ok(fitted) => fitted,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    };
+
+    println!("spread {spread}");
+    println!("base-rating {base_rating}");
+
+    if fit.apply {
+        data.config.spread = spread;
+        data.config.base_rating = base_rating;
+        write_data(path, &data);
+    }
+}
+
+fn distribution(path: &Path, distribution: Distribution) {
+    let data = read_data(path);
+
+    if !(distribution.bin_width > 0.0 && distribution.bin_width.is_finite()) {
+        eprintln!("The bin width must be a positive number");
+        process::exit(1);
+    }
+
+    let bins = evaluate(&data).histogram(distribution.bin_width, &data.config);
+    let (Some(first), Some(last)) = (bins.keys().next(), bins.keys().next_back()) else {
+        return;
+    };
+
+    // Empty bins in between are printed too, so that gaps are visible
+    for bin in *first..=*last {
+        let count = bins.get(&bin).copied().unwrap_or(0);
+        let line = format!(
+            "{:7.1} {count:3} {}",
+            bin as f64 * distribution.bin_width,
+            "#".repeat(count)
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+fn balance(path: &Path, balance: Balance) {
+    let data = read_data(path);
+    let eval = evaluate(&data);
+
+    let candidates: Vec<String> = if balance.candidates.is_empty() {
+        eval.ratings.keys().cloned().collect()
+    } else {
+        let mut candidates = Vec::new();
+        for pattern in &balance.candidates {
+            let Some(player) = try_find_name(&data, pattern) else {
+                process::exit(1);
+            };
+            candidates.push(player);
+        }
+        candidates
+    };
+    let candidates: Vec<&str> = candidates.iter().map(String::as_str).collect();
+
+    let Some(triple) = eval.most_balanced(&candidates) else {
+        eprintln!("At least three players are needed");
+        process::exit(1);
+    };
+
+    for player in triple {
+        println!(
+            "{:6.1} {player}",
+            data.config.rating_to_listed_display(eval.ratings[player])
+        );
+    }
+}
+
+fn reset_player(path: &Path, param: ResetPlayer) {
+    let mut data = read_data(path);
+
+    let Some(player) = try_find_name(&data, &param.player) else {
+        return;
+    };
+
     let rating = param.rating.unwrap_or(data.config.base_rating);
+    if let Err(err) = data.set_rating_display(player, rating) {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+
+    write_data(path, &data);
+}
+
+fn ratings(path: &Path, param: Ratings) {
+    if !param.watch {
+        print_ratings(read_data(path), &param);
+        return;
+    }
+
+    ultira::watch(path, Duration::from_millis(500), |result| {
+        match result {
+            Ok(data) => {
+                // Clear the screen and move the cursor to the top
+                print!("\x1B[2J\x1B[H");
+                print_ratings(data, &param);
+            }
+            Err(err) => eprintln!("Couldn't read the file, showing the last readable state: {err}"),
+        }
+        true
+    });
+}
+
+fn print_ratings(mut data: ultira::Data, param: &Ratings) {
+    if let Some(alpha) = param.alpha {
+        data = data.with_fixed_α(data.config.α_from_display(alpha));
+    }
+
+    let anchor = match &param.relative_to {
+        Some(name) => {
+            let Some(anchor) = try_find_name(&data, name) else {
+                process::exit(1);
+            };
+            Some(anchor)
+        }
+        None => None,
+    };
+
+    let mut min_games = param.min_games.unwrap_or(data.config.ranking_min_games);
+    let mut eval = match (&param.season, param.up_to) {
+        (Some(season), _) => {
+            let Some(eval) = data.evaluate_season(season) else {
+                eprintln!("No season named '{season}'");
+                process::exit(1);
+            };
+            min_games = min_games.max(1);
+            eval
+        }
+        (None, Some(up_to)) => {
+            if up_to > data.history.len() {
+                eprintln!("The history only has {} changes", data.history.len());
+                process::exit(1);
+            }
+            data.evaluate_up_to(up_to)
+        }
+        (None, None) if param.timings => {
+            let (eval, timings) = data.evaluate_timed();
+            eprintln!("{timings}");
+            eval
+        }
+        (None, None) => evaluate(&data),
+    };
+    if let Some(anchor) = &anchor {
+        eval.anchor_at(anchor);
+    }
+
+    let precision = if param.internal {
+        param.precision.or(Some(6))
+    } else {
+        param.precision
+    };
+    let display = data
+        .config
+        .display
+        .with_overrides(param.decimal_comma, precision, param.sort);
+
+    let mut leaderboard = eval.leaderboard(min_games);
+    if param.active {
+        let since = param
+            .inactive
+            .map(|days| chrono::Local::now().date_naive() - chrono::Days::new(days));
+        let active = data.active_players(since);
+        leaderboard.retain(|(player, _)| active.contains(*player));
+    }
+    if display.sort == ultira::RatingSort::Name {
+        leaderboard.sort_unstable_by_key(|(player, _)| *player);
+    }
+
+    let participation = if param.breakdown {
+        data.participation()
+    } else {
+        HashMap::new()
+    };
+
+    for (player, rating) in leaderboard {
+        let rating = match (&anchor, param.internal) {
+            (Some(_), true) => display.format_signed(rating),
+            (Some(_), false) => display.format_signed(rating * data.config.spread),
+            (None, true) => display.format(rating),
+            (None, false) => display.format(data.config.rating_to_listed_display(rating)),
+        };
+
+        match participation.get(player) {
+            Some(counts) => {
+                let counts: Vec<String> = counts
+                    .iter()
+                    .map(|(variant, count)| format!("{variant} {count}"))
+                    .collect();
+                println!("{rating} {player} ({})", counts.join(", "));
+            }
+            None => println!("{rating} {player}"),
+        }
+    }
+}
+
+fn season(path: &Path, season: Season) {
+    let mut data = read_data(path);
+
+    let date = season
+        .date
+        .unwrap_or_else(|| chrono::Local::now().date_naive());
+    data.start_season(season.name, Some(date));
+
+    write_data(path, &data);
+}
+
+fn compact(path: &Path, compact: Compact) {
+    let mut data = read_data(path);
+
+    for season in data.seasons_cut_by_compaction(compact.before) {
+        eprintln!(
+            "warning: the standings of season '{season}' will only count games from {} on",
+            data.config.format_date(compact.before)
+        );
+    }
+
+    let question = format!(
+        "Are you sure you want to compact history before {} inside {}? YOU CANNOT UNDO THIS OPERATION.",
+        compact.before,
+        path.to_string_lossy()
+    );
+    if !compact.no_confirm && !confirm(&data.config, &question) {
+        return;
+    }
+
+    let length_before = data.history.len();
+    let removed = data.compact_before(compact.before);
+
+    write_data(path, &data);
+
+    println!(
+        "Compacted {removed} changes into {}",
+        data.history.len() + removed - length_before
+    );
+}
+
+fn rollback(path: &Path, rollback: Rollback) {
+    let mut data = read_data(path);
+
+    let removed = data.rollback_to(rollback.to);
+    if removed == 0 {
+        println!("Nothing is dated after {}, nothing to do", rollback.to);
+        return;
+    }
+
+    let question = format!(
+        "Are you sure you want to remove the last {removed} changes from {}? YOU CANNOT UNDO THIS OPERATION.",
+        path.to_string_lossy()
+    );
+    if !rollback.no_confirm && !confirm(&data.config, &question) {
+        return;
+    }
+
+    write_data(path, &data);
+
+    println!("Removed {removed} changes");
+}
+
+fn dedup(path: &Path, dedup: Dedup) {
+    let mut data = read_data(path);
+
+    let removed = data.dedup_adjacent(dedup.epsilon);
+    println!("Removed {removed} duplicate changes");
+
+    if removed > 0 {
+        write_data(path, &data);
+    }
+}
+
+fn repair(path: &Path) {
+    match ultira::repair(path) {
+        Ok(ultira::Repair::Intact) => {
+            println!("{} is readable, nothing to do", path.to_string_lossy())
+        }
+        Ok(ultira::Repair::FromBackup(backup)) => println!(
+            "Restored {} from {}",
+            path.to_string_lossy(),
+            backup.to_string_lossy()
+        ),
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn check(path: &Path, check: Check) {
+    let data = read_data(path);
+
+    let mut problems = data.check();
+    // Evaluation panics on referential problems
+    if check.conservation && problems.is_empty() {
+        problems.extend(data.check_conservation(CONSERVATION_EPS));
+    }
+    if check.determinism && problems.is_empty() {
+        problems.extend(data.check_determinism());
+    }
+    problems.extend(data.check_zero_sum());
+
+    let warnings = data.check_duplicate_players();
+
+    if check.format == ultira::ReportFormat::Json {
+        if check.strict {
+            problems.extend(warnings);
+        }
+        println!("{}", ultira::to_json(&problems).unwrap());
+
+        if !problems.is_empty() {
+            process::exit(1);
+        }
+        return;
+    }
+
+    if problems.is_empty() && warnings.is_empty() {
+        println!("No problems found");
+        return;
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+        if check.fix {
+            print_fix_hint(&data, problem);
+        }
+    }
+    for warning in &warnings {
+        println!("warning: {warning}");
+    }
+
+    if !problems.is_empty() || check.strict {
+        process::exit(1);
+    }
+}
+
+/// None of the problems can be fixed automatically yet, so this only explains what to edit
+fn print_fix_hint(data: &ultira::Data, problem: &ultira::Problem) {
+    let Some(i) = problem.history_index else {
+        return;
+    };
+
+    if let (ultira::ProblemKind::NonZeroSum, ultira::Change::Play(play)) =
+        (&problem.kind, &data.history[i])
+    {
+        let scores: Vec<String> = play
+            .outcomes
+            .iter()
+            .map(|o| format!("{} {}", o.player, o.score))
+            .collect();
+        println!(
+            "  can't be fixed automatically, edit the scores of history[{i}] ({}) to sum to 0",
+            scores.join(", ")
+        );
+    }
+}
+
+fn diff_file(path: &Path, param: DiffFile) {
+    let data = read_data(path);
+    let other = read_data(&param.other);
+
+    let eval = evaluate(&data);
+    let other_eval = evaluate(&other);
+
+    let mut diff: Vec<(String, f64)> = eval.diff(&other_eval).into_iter().collect();
+    diff.sort_unstable_by(|(_player_a, diff_a), (_player_b, diff_b)| {
+        diff_a.abs().partial_cmp(&diff_b.abs()).unwrap().reverse()
+    });
+
+    for (player, diff) in diff {
+        println!("{:+6.1} {}", diff * data.config.spread, player);
+    }
+
+    for player in eval.players_missing_from(&other_eval) {
+        println!("only in {}: {}", path.to_string_lossy(), player);
+    }
+    for player in other_eval.players_missing_from(&eval) {
+        println!("only in {}: {}", param.other.to_string_lossy(), player);
+    }
+}
+
+fn why(path: &Path, why: Why) {
+    let data = read_data(path);
+
+    let Some(player) = try_find_name(&data, &why.player) else {
+        return;
+    };
+
+    let events = data.rating_events(&player);
+
+    for event in &events {
+        let date = match event.change.date() {
+            Some(date) => data.config.format_date(date),
+            None => "-".to_owned(),
+        };
+
+        match event.delta() {
+            Some(delta) => print!(
+                "{date:10} {:12} {:+6.1}",
+                event.change.variant_name(),
+                delta * data.config.spread
+            ),
+            None => print!(
+                "{date:10} {:12} {:6.1}",
+                event.change.variant_name(),
+                data.config.rating_to_display(event.after)
+            ),
+        }
+
+        match event.change {
+            ultira::Change::Play(ultira::Play {
+                note: Some(note), ..
+            }) => println!(" {note}"),
+            _ => println!(),
+        }
+    }
+
+    let rating = evaluate(&data).ratings[&player];
+    let centering = rating - events.last().unwrap().after;
+
+    if centering != 0.0 {
+        println!(
+            "{:10} {:12} {:+6.1}",
+            "",
+            "auto_center",
+            centering * data.config.spread
+        );
+    }
+
+    println!(
+        "{:10} {:12} {:6.1}",
+        "",
+        "current",
+        data.config.rating_to_display(rating)
+    );
+}
+
+fn history(path: &Path, history: History) {
+    let data = read_data(path);
+
+    if history.narrative {
+        for change in &data.history {
+            println!("{}", change.narrative(&data.config));
+        }
+        return;
+    }
+
+    for (i, change) in data.history.iter().enumerate() {
+        let date = match change.date() {
+            Some(date) => data.config.format_date(date),
+            None => "-".to_owned(),
+        };
+
+        let details = match change {
+            ultira::Change::AddPlayer(addition) => format!(
+                "{} {:.1}",
+                addition.name,
+                data.config.rating_to_display(addition.rating)
+            ),
+            ultira::Change::Play(play) => {
+                let outcomes: Vec<String> = play
+                    .outcomes
+                    .iter()
+                    .map(|o| format!("{} {}", o.player, o.score))
+                    .collect();
+                let mut details = format!("{} games: {}", play.games(), outcomes.join(", "));
+                if let Some(session) = play.session {
+                    details += &format!(" (session {session})");
+                }
+                if let Some(note) = &play.note {
+                    details += &format!(" ({note})");
+                }
+                details
+            }
+            ultira::Change::AdjustAlpha(adjust) => {
+                let mut details = format!("{}", data.config.α_to_display(adjust.value));
+                if let Some(reason) = &adjust.reason {
+                    details += &format!(" ({reason})");
+                }
+                details
+            }
+            ultira::Change::SetRating(set) => format!(
+                "{} {:.1}",
+                set.player,
+                data.config.rating_to_display(set.rating)
+            ),
+            ultira::Change::SeasonBoundary(boundary) => boundary.name.clone(),
+            _ => String::new(),
+        };
 
-    data.add_player_display(param.player, rating);
+        let α = data.config.α_to_display(data.α_at(i));
 
-    ultira::write_data(path, &data).unwrap();
+        println!(
+            "{i:5} {date:10} {α:7.3} {:15} {details}",
+            change.variant_name()
+        );
+    }
 }
 
-fn ratings(path: &Path) {
+fn references(path: &Path, references: References) {
     let data = read_data(path);
 
-    let mut ratings: Vec<(String, f64)> = data.evaluate().ratings.into_iter().collect();
+    let Some(player) = try_find_name(&data, &references.player) else {
+        process::exit(1);
+    };
 
-    ratings.sort_unstable_by(|(_player_a, rating_a), (_player_b, rating_b)| {
-        rating_a.partial_cmp(rating_b).unwrap().reverse()
-    });
+    for i in data.references(&player) {
+        println!("{i:5} {}", data.history[i].variant_name());
+    }
+}
+
+fn trends(path: &Path) {
+    let data = read_data(path);
+    let players = data.player_names();
+
+    let width = players.iter().map(|player| player.chars().count()).max();
+    for player in players {
+        println!(
+            "{player:width$} {}",
+            data.sparkline(&player),
+            width = width.unwrap_or(0)
+        );
+    }
+}
+
+fn movers(path: &Path, movers: Movers) {
+    let data = read_data(path);
+    let changes = data.movers(movers.since);
+
+    let gainers = changes.iter().filter(|(_, delta)| *delta > 0.0);
+    let losers = changes.iter().rev().filter(|(_, delta)| *delta < 0.0);
+
+    println!("Gainers:");
+    for (player, delta) in gainers.take(movers.top) {
+        println!("{:+6.1} {player}", delta);
+    }
+
+    println!("Losers:");
+    for (player, delta) in losers.take(movers.top) {
+        println!("{:+6.1} {player}", delta);
+    }
+}
+
+fn dates(path: &Path) {
+    let data = read_data(path);
+
+    for (date, count) in data.activity_dates() {
+        println!("{} {count}", data.config.format_date(date));
+    }
+}
+
+fn inflation(path: &Path) {
+    let data = read_data(path);
 
-    for (player, rating) in ratings {
-        println!("{:6.1} {}", data.config.rating_to_display(rating), player);
+    for (point, average) in data.inflation() {
+        println!(
+            "{} {:+.4}",
+            data.config.format_time_point(point),
+            average * data.config.spread
+        );
     }
 }
 
 fn adjust(path: &Path, param: Param) {
     let mut data = read_data(path);
+    let is_set = param.is_set();
 
     match param {
         Param::Spread { new_value: None } => println!("{}", data.config.spread),
         Param::Spread {
             new_value: Some(val),
-        } => data.config.spread = val,
-        Param::ScoreMultiplier { new_value: None } => {
-            println!("{}", data.config.α_to_display(data.evaluate().α))
+        } => {
+            data.config.spread = val;
+            if let Err(err) = data.config.check_spread() {
+                eprintln!("{err}");
+                process::exit(1);
+            }
         }
+        Param::ScoreMultiplier {
+            new_value: None,
+            internal: false,
+            ..
+        } => println!("{}", data.config.α_to_display(evaluate(&data).α)),
+        Param::ScoreMultiplier {
+            new_value: None,
+            internal: true,
+            ..
+        } => println!("{}", evaluate(&data).α),
         Param::ScoreMultiplier {
             new_value: Some(val),
-        } => data.adjust_score_multiplier(val),
+            reason,
+            ..
+        } => data.adjust_α_with(ultira::AdjustAlpha {
+            value: data.config.α_from_display(val),
+            date: Some(chrono::Local::now().date_naive()),
+            reason,
+        }),
         Param::BaseRating { new_value: None } => println!("{}", data.config.base_rating),
         Param::BaseRating {
             new_value: Some(val),
         } => data.config.base_rating = val,
+        Param::NewPlayerPolicy { new_value: None } => {
+            println!("{}", data.config.new_player_policy)
+        }
+        Param::NewPlayerPolicy {
+            new_value: Some(val),
+        } => data.config.new_player_policy = val,
+        Param::RankingMinGames { new_value: None } => {
+            println!("{}", data.config.ranking_min_games)
+        }
+        Param::RankingMinGames {
+            new_value: Some(val),
+        } => data.config.ranking_min_games = val,
+        Param::AutoCenter { new_value: None } => println!("{}", data.config.auto_center),
+        Param::AutoCenter {
+            new_value: Some(val),
+        } => data.config.auto_center = val,
+        Param::MaxGameCount { new_value: None } => println!("{}", data.config.max_game_count),
+        Param::MaxGameCount {
+            new_value: Some(val),
+        } => data.config.max_game_count = val,
+        Param::MaxRatingChangePerPlay {
+            new_value: None,
+            unset: false,
+        } => match data.config.max_rating_change_per_play {
+            Some(max) => println!("{max}"),
+            None => println!("unlimited"),
+        },
+        Param::MaxRatingChangePerPlay {
+            new_value: Some(val),
+            ..
+        } => {
+            if let Err(err) = ultira::Config::check_max_rating_change(val) {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+
+            data.config.max_rating_change_per_play = Some(val);
+        }
+        Param::MaxRatingChangePerPlay {
+            new_value: None,
+            unset: true,
+        } => data.config.max_rating_change_per_play = None,
+        Param::RatingFloor {
+            new_value: None,
+            unset: false,
+        } => match data.config.rating_floor {
+            Some(floor) => println!("{floor}"),
+            None => println!("unlimited"),
+        },
+        Param::RatingFloor {
+            new_value: Some(val),
+            ..
+        } => data.config.rating_floor = Some(val),
+        Param::RatingFloor {
+            new_value: None,
+            unset: true,
+        } => data.config.rating_floor = None,
+        Param::RatingCeiling {
+            new_value: None,
+            unset: false,
+        } => match data.config.rating_ceiling {
+            Some(ceiling) => println!("{ceiling}"),
+            None => println!("unlimited"),
+        },
+        Param::RatingCeiling {
+            new_value: Some(val),
+            ..
+        } => data.config.rating_ceiling = Some(val),
+        Param::RatingCeiling {
+            new_value: None,
+            unset: true,
+        } => data.config.rating_ceiling = None,
+        Param::ClampInternalRatings { new_value: None } => {
+            println!("{}", data.config.clamp_internal_ratings)
+        }
+        Param::ClampInternalRatings {
+            new_value: Some(val),
+        } => data.config.clamp_internal_ratings = val,
+        Param::StorePrecision {
+            new_value: None,
+            unset: false,
+        } => match data.config.store_precision {
+            Some(precision) => println!("{precision}"),
+            None => println!("full"),
+        },
+        Param::StorePrecision {
+            new_value: Some(val),
+            ..
+        } => data.config.store_precision = Some(val),
+        Param::StorePrecision {
+            new_value: None,
+            unset: true,
+        } => data.config.store_precision = None,
+        Param::ConfirmWords { new_value } if new_value.is_empty() => {
+            println!("{}", data.config.confirm_words.join(" "))
+        }
+        Param::ConfirmWords { new_value } => {
+            if new_value.iter().any(|word| word.trim().is_empty()) {
+                eprintln!("Confirmation words can't be empty");
+                process::exit(1);
+            }
+
+            data.config.confirm_words = new_value;
+        }
+        Param::DateFormat { new_value: None } => println!("{}", data.config.date_format),
+        Param::DateFormat {
+            new_value: Some(val),
+        } => {
+            if let Err(e) = ultira::Config::check_date_format(&val) {
+                eprintln!("{e}");
+                process::exit(1);
+            }
+
+            data.config.date_format = val;
+        }
+        Param::Schema => print!("{}", data.config.schema().unwrap()),
+        Param::PlayerAlpha { player, new_value } => {
+            let Some(player) = try_find_name(&data, &player) else {
+                return;
+            };
+
+            match new_value {
+                None => println!("{}", data.config.player_α(&player, 1.0)),
+                Some(val) => {
+                    data.config.player_alpha.insert(player, val);
+                }
+            }
+        }
+    }
+
+    if let Err(err) = data
+        .check_finite()
+        .and_then(|()| data.config.check_display_range())
+    {
+        eprintln!("{err}");
+        process::exit(1);
     }
 
-    ultira::write_data(path, &data).unwrap();
+    if is_set {
+        write_data(path, &data);
+    }
 }
 
 fn undo(path: &Path, undo: Undo) {
@@ -274,19 +2004,31 @@ fn undo(path: &Path, undo: Undo) {
     if !undo.no_confirm {
         println!("Last element of history: {:#?}", last);
 
-        println!(
-            "Are you sure you want to undo last action affecting history (see above) inside {}? (y/N)",
-            path.to_string_lossy()
-        );
-
-        if !confirm() {
+        if !confirm(
+            &data.config,
+            &format!(
+                "Are you sure you want to undo last action affecting history (see above) inside {}?",
+                path.to_string_lossy()
+            ),
+        ) {
             return;
         }
     }
 
     data.history.pop();
 
-    ultira::write_data(path, &data).unwrap();
+    write_data(path, &data);
+}
+
+fn set_date(path: &Path, set: SetDate) {
+    let mut data = read_data(path);
+
+    if let Err(err) = data.set_date(set.index, set.date) {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+
+    write_data(path, &data);
 }
 
 fn rename_player(path: &Path, rename: RenamePlayer) {
@@ -296,40 +2038,410 @@ fn rename_player(path: &Path, rename: RenamePlayer) {
         return;
     };
 
-    if data
-        .evaluate()
-        .ratings
-        .keys()
-        .any(|name| *name == rename.new_name)
+    if let Err(err) = ultira::check_name(&rename.new_name) {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+
+    if old_name == rename.new_name {
+        println!("{old_name} is already called {old_name}, nothing to do");
+        return;
+    }
+
+    let question = format!(
+        "Name '{}' already exists. YOU CANNOT UNDO THIS OPERATION. Are you sure you want to MERGE these two players into one?",
+        rename.new_name
+    );
+    if data.player_names().contains(&rename.new_name) && !confirm(&data.config, &question) {
+        return;
+    }
+
+    data.rename(&old_name, &rename.new_name);
+
+    write_data(path, &data);
+
+    println!("Renamed {old_name} to {}", rename.new_name);
+}
+
+fn snapshot(path: &Path, snapshot: Snapshot) {
+    let data = read_data(path);
+
+    if !snapshot.no_confirm
+        && snapshot.out_file.exists()
+        && !confirm(
+            &data.config,
+            &format!(
+                "Are you sure you want to override {}?",
+                snapshot.out_file.to_string_lossy()
+            ),
+        )
     {
-        println!(
-            "Name '{}' already exists. YOU CANNOT UNDO THIS OPERATION. Are you sure you want to MERGE these two players into one? (y/N)",
-            rename.new_name
+        return;
+    }
+
+    if let Err(err) = ultira::write_data(&snapshot.out_file, &data.snapshot()) {
+        eprintln!(
+            "Couldn't write {}: {err}",
+            snapshot.out_file.to_string_lossy()
         );
+        process::exit(1);
+    }
+}
 
-        if !confirm() {
-            return;
+fn export_history(path: &Path, export: ExportHistory) {
+    let data = read_data(path);
+
+    let json = ultira::to_json(&data.history).unwrap();
+
+    fs::write(&export.file, json).unwrap();
+}
+
+fn export_ratings(path: &Path, export: ExportRatings) {
+    let data = read_data(path);
+
+    if export.format == ultira::TableFormat::Markdown {
+        if export.append
+            || !export.players.is_empty()
+            || export.delimiter.is_some()
+            || export.decimal_comma
+        {
+            eprintln!(
+                "--append, --player, --delimiter and --decimal-comma only apply to --format tsv"
+            );
+            process::exit(1);
         }
+
+        fs::write(&export.file, data.standings_markdown()).unwrap();
+        return;
     }
 
-    data.rename(&old_name, &rename.new_name);
+    let mut options = ultira::TableOptions {
+        layout: export.layout,
+        players: None,
+        delimiter: export.delimiter.unwrap_or('\t'),
+        decimal_comma: export.decimal_comma,
+    };
 
-    ultira::write_data(path, &data).unwrap();
+    if let Err(e) = options.check() {
+        eprintln!("{e}");
+        process::exit(1);
+    }
 
-    println!("Renamed {old_name} to {}", rename.new_name);
+    if export.append && export.file.exists() {
+        let rows = fs::read_to_string(&export.file)
+            .map_err(Into::into)
+            .and_then(|existing| data.ratings_table_append(&options, &existing));
+
+        match rows {
+            Ok(rows) => {
+                let mut file = fs::OpenOptions::new()
+                    .append(true)
+                    .open(&export.file)
+                    .unwrap();
+                file.write_all(rows.as_bytes()).unwrap();
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    options.players = if export.players.is_empty() {
+        None
+    } else {
+        let mut players = Vec::new();
+        for pattern in &export.players {
+            let Some(player) = try_find_name(&data, pattern) else {
+                return;
+            };
+            players.push(player);
+        }
+        Some(players)
+    };
+
+    fs::write(&export.file, data.ratings_table(&options)).unwrap();
+}
+
+fn export_graph(path: &Path, export: ExportGraph) {
+    let data = read_data(path);
+
+    fs::write(&export.file, data.to_dot()).unwrap();
+}
+
+fn export_timelines(path: &Path, export: ExportTimelines) {
+    let data = read_data(path);
+
+    let mut files = BTreeMap::new();
+    for player in data.player_names() {
+        let file_name = format!("{}.tsv", ultira::file_name_for(&player));
+        if let Some(other) = files.insert(file_name.clone(), player.clone()) {
+            eprintln!("{other} and {player} would both be written to {file_name}");
+            process::exit(1);
+        }
+    }
+
+    fs::create_dir_all(&export.dir).unwrap();
+    for (file_name, player) in &files {
+        fs::write(
+            export.dir.join(file_name),
+            data.player_timeline_table(player),
+        )
+        .unwrap();
+    }
+
+    println!(
+        "Wrote {} files to {}",
+        files.len(),
+        export.dir.to_string_lossy()
+    );
+}
+
+fn import_history(path: &Path, import: ImportHistory) {
+    let mut data = read_data(path);
+
+    let mut changes: Vec<ultira::Change> = match fs::read_to_string(&import.file)
+        .map_err(Into::into)
+        .and_then(|str| ultira::from_json(&str))
+    {
+        Ok(changes) => changes,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    };
+
+    for (i, change) in changes.iter_mut().enumerate() {
+        if let ultira::Change::Play(play) = change {
+            play.canonicalize();
+            if let Err(err) = data.config.check_play(play) {
+                eprintln!("Imported change {i} is invalid, aborting: {err}");
+                process::exit(1);
+            }
+        }
+    }
+
+    if import.replace {
+        data.history = changes;
+    } else {
+        data.history.extend(changes);
+    }
+
+    let problems = data.check();
+    if !problems.is_empty() {
+        eprintln!("Imported history is invalid, aborting:");
+        for problem in problems {
+            eprintln!("{problem}");
+        }
+        process::exit(1);
+    }
+
+    write_data(path, &data);
+}
+
+fn import_elo(path: &Path, import: ImportElo) {
+    let mut data = read_data(path);
+
+    let count = match fs::read_to_string(&import.file)
+        .map_err(Into::into)
+        .and_then(|csv| data.import_elo(&csv, import.center, import.k))
+    {
+        Ok(count) => count,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    };
+
+    println!("Added {count} players");
+
+    write_data(path, &data);
+}
+
+fn import_log(path: &Path, import: ImportLog) {
+    let mut data = read_data(path);
+
+    let plays = match fs::read_to_string(&import.file)
+        .map_err(Into::into)
+        .and_then(|log| ultira::parse_play_log(&log))
+    {
+        Ok(plays) => plays,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    };
+
+    let count = plays.len();
+    for (line, mut play) in plays {
+        if let Err(err) = data.config.check_game_count(play.game_count) {
+            eprintln!("line {line}: {err}");
+            process::exit(1);
+        }
+
+        for outcome in &mut play.outcomes {
+            let Some(player) = try_find_name(&data, &outcome.player) else {
+                eprintln!("line {line}: nothing was imported");
+                process::exit(1);
+            };
+            outcome.player = player;
+        }
+
+        data.play(play);
+    }
+
+    println!("Added {count} plays");
+
+    write_data(path, &data);
 }
 
 fn main() {
     let args: Cli = Cli::parse();
 
+    OPTIONS
+        .set(Options {
+            match_mode: if args.prefer_prefix {
+                ultira::MatchMode::PreferPrefix
+            } else {
+                ultira::MatchMode::StrictExact
+            },
+            read_only: args.read_only,
+            interactive: args.interactive,
+            verify_writes: args.verify_writes,
+            yes: args.yes,
+            #[cfg(feature = "parallel")]
+            parallel: args.parallel,
+        })
+        .unwrap();
+
+    // Held until the command finishes, exiting releases it too
+    let mut _lock = None;
+
+    if args.command.is_mutating() {
+        if args.read_only {
+            eprintln!("This command may modify the file, which is opened read-only");
+            process::exit(1);
+        }
+
+        match ultira::lock(&args.file, args.wait_for_lock) {
+            Ok(lock) => _lock = Some(lock),
+            Err(err) => {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+        }
+
+        audit(&args.file, &args.command);
+    }
+
     match args.command {
         Command::Play(p) => play(&args.file, p),
+        Command::Play2(p) => play2(&args.file, p),
+        Command::Draw(p) => draw(&args.file, p),
         Command::New(p) => new(&args.file, p),
+        Command::Generate(p) => generate(&args.file, p),
         Command::AddPlayer(p) => add_player(&args.file, p),
-        Command::Ratings => ratings(&args.file),
+        Command::Rank(p) => rank(&args.file, p),
+        Command::Odds(p) => odds(&args.file, p),
+        Command::Balance(p) => balance(&args.file, p),
+        Command::Distribution(p) => distribution(&args.file, p),
+        Command::FitDisplay(p) => fit_display(&args.file, p),
+        Command::Intransitivity(p) => intransitivity(&args.file, p),
+        Command::HeadToHead(p) => head_to_head(&args.file, p),
+        Command::ResetPlayer(p) => reset_player(&args.file, p),
+        Command::Ratings(p) => ratings(&args.file, p),
+        Command::Season(p) => season(&args.file, p),
         Command::Config(a) => adjust(&args.file, a.param),
         Command::Undo(p) => undo(&args.file, p),
+        Command::SetDate(p) => set_date(&args.file, p),
         Command::RenamePlayer(p) => rename_player(&args.file, p),
+        Command::Compact(p) => compact(&args.file, p),
+        Command::Rollback(p) => rollback(&args.file, p),
+        Command::Snapshot(p) => snapshot(&args.file, p),
+        Command::Dedup(p) => dedup(&args.file, p),
+        Command::Repair => repair(&args.file),
+        Command::Check(p) => check(&args.file, p),
+        Command::DiffFile(p) => diff_file(&args.file, p),
+        Command::Why(p) => why(&args.file, p),
+        Command::History(p) => history(&args.file, p),
+        Command::References(p) => references(&args.file, p),
+        Command::Trends => trends(&args.file),
+        Command::Movers(p) => movers(&args.file, p),
+        Command::Dates => dates(&args.file),
+        Command::Inflation => inflation(&args.file),
+        Command::ExportHistory(p) => export_history(&args.file, p),
+        Command::ExportRatings(p) => export_ratings(&args.file, p),
+        Command::ExportGraph(p) => export_graph(&args.file, p),
+        Command::ExportTimelines(p) => export_timelines(&args.file, p),
+        Command::ImportHistory(p) => import_history(&args.file, p),
+        Command::ImportElo(p) => import_elo(&args.file, p),
+        Command::ImportLog(p) => import_log(&args.file, p),
+    }
+}
+
+/// Logs the command if the file has an audit log configured, before it gets executed
+fn audit(path: &Path, command: &Command) {
+    let Ok(data) = ultira::read_data(path) else {
+        return;
+    };
+
+    let Some(audit_log) = &data.config.audit_log else {
+        return;
+    };
+
+    if let Err(err) = ultira::append_audit(audit_log, &format!("{command:?}")) {
+        eprintln!("Couldn't write audit log: {err}");
+        process::exit(1);
+    }
+}
+
+/// Evaluates with a progress bar on long histories, or in parallel, if enabled
+fn evaluate(data: &ultira::Data) -> ultira::Evaluation {
+    #[cfg(feature = "parallel")]
+    if options().parallel {
+        return data.evaluate_parallel();
+    }
+
+    #[cfg(feature = "progress")]
+    {
+        use std::io::IsTerminal;
+
+        if data.history.len() > PROGRESS_THRESHOLD && io::stderr().is_terminal() {
+            let bar = indicatif::ProgressBar::new(data.history.len() as u64);
+            let eval = data.evaluate_with_progress(|done| bar.set_position(done as u64));
+            bar.finish_and_clear();
+
+            return eval;
+        }
+    }
+
+    data.evaluate()
+}
+
+fn write_data(path: &Path, data: &ultira::Data) {
+    if options().read_only {
+        eprintln!(
+            "Can't write {}, it's opened read-only",
+            path.to_string_lossy()
+        );
+        process::exit(1);
+    }
+
+    if let Err(err) = ultira::write_data(path, data) {
+        eprintln!("Can't write {}: {err}", path.to_string_lossy());
+        process::exit(1);
+    }
+
+    if options().verify_writes {
+        if let Err(err) = ultira::verify_write(path, data) {
+            eprintln!(
+                "{} doesn't read back as written, it may be damaged: {err}",
+                path.to_string_lossy()
+            );
+            process::exit(1);
+        }
     }
 }
 
@@ -344,8 +2456,8 @@ fn read_data(path: &Path) -> ultira::Data {
 }
 
 fn try_find_name(data: &ultira::Data, name: &str) -> Option<String> {
-    let eval = data.evaluate();
-    let matches = eval.matching_names(name);
+    let eval = evaluate(data);
+    let matches = eval.matching_names_with(name, options().match_mode);
 
     match matches.len() {
         0 => {
@@ -353,6 +2465,18 @@ fn try_find_name(data: &ultira::Data, name: &str) -> Option<String> {
             None
         }
         1 => Some(matches[0].to_owned()),
+        _ if options().interactive => {
+            println!("Name '{name}' matches multiple names, which one did you mean?");
+            for (i, name) in matches.iter().enumerate() {
+                println!("{}: {name}", i + 1);
+            }
+
+            let picked = ultira::pick(&matches, &mut io::stdin().lock());
+            if picked.is_none() {
+                println!("No name was picked, aborting...");
+            }
+            picked.map(str::to_owned)
+        }
         _ => {
             println!("Name '{name}' match multiple names, aborting. Matched names are:");
             for name in matches {
@@ -363,14 +2487,31 @@ fn try_find_name(data: &ultira::Data, name: &str) -> Option<String> {
     }
 }
 
-fn confirm() -> bool {
+/// Asks `question`, hinting at and accepting the `confirm_words` of `config`
+fn confirm(config: &ultira::Config, question: &str) -> bool {
+    println!("{question} {}", config.confirmation_hint());
+
+    if options().yes {
+        return true;
+    }
+
     let mut ans = String::new();
     io::stdin().read_line(&mut ans).unwrap();
 
-    if ans.trim() == "y" || ans.trim() == "Y" {
+    if config.is_confirmation(&ans) {
         true
     } else {
-        println!("Confirmation didn't match 'y' or 'Y', aborting...");
+        println!(
+            "Confirmation didn't match any of '{}', aborting...",
+            config.confirm_words.join("', '")
+        );
         false
     }
 }
+
+/// The config of the file about to be overwritten, the default if it can't be read
+fn existing_config(path: &Path) -> ultira::Config {
+    ultira::read_data(path)
+        .map(|data| data.config)
+        .unwrap_or_default()
+}
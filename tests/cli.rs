@@ -0,0 +1,414 @@
+use std::{fs, path::PathBuf, process::Command};
+
+fn ultira(file: &PathBuf, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_ultira"))
+        .arg("--file")
+        .arg(file)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ultira-cli-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("ultira.toml")
+}
+
+#[test]
+fn read_only() {
+    let file = temp_file("read-only");
+    assert!(ultira(&file, &["new", "-n"]).status.success());
+    let contents = fs::read(&file).unwrap();
+    let modified = fs::metadata(&file).unwrap().modified().unwrap();
+
+    let get = ultira(&file, &["--read-only", "config", "spread"]);
+    assert!(get.status.success());
+    assert_eq!(String::from_utf8(get.stdout).unwrap(), "50\n");
+
+    let set = ultira(&file, &["--read-only", "config", "spread", "60"]);
+    assert!(!set.status.success());
+
+    let add = ultira(&file, &["add-player", "A", "--read-only"]);
+    assert!(!add.status.success());
+
+    assert_eq!(fs::read(&file).unwrap(), contents);
+    assert_eq!(fs::metadata(&file).unwrap().modified().unwrap(), modified);
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn new_with_config() {
+    let file = temp_file("new-with-config");
+    let new = ultira(
+        &file,
+        &[
+            "new", "-n", "--spread", "100", "--base", "1000", "--alpha", "0.05",
+        ],
+    );
+    assert!(new.status.success());
+
+    let data = ultira::read_data(&file).unwrap();
+    assert_eq!(data.config.spread, 100.0);
+    assert_eq!(data.config.base_rating, 1000.0);
+    assert_eq!(data.config.starting_alpha, 0.05);
+    assert_eq!(
+        data.config.max_game_count,
+        ultira::Config::default().max_game_count
+    );
+
+    let contents = fs::read(&file).unwrap();
+    for invalid in ["--alpha=nan", "--alpha=0", "--alpha=-0.02", "--base=inf"] {
+        let new = ultira(&file, &["new", "-n", invalid]);
+        assert!(!new.status.success());
+        assert!(String::from_utf8(new.stderr)
+            .unwrap()
+            .contains("it must be"));
+        assert_eq!(fs::read(&file).unwrap(), contents);
+    }
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn auto_add() {
+    let file = temp_file("auto-add");
+    assert!(ultira(&file, &["new", "-n"]).status.success());
+    for player in ["Anna", "Bob"] {
+        assert!(ultira(&file, &["add-player", player]).status.success());
+    }
+
+    let without = ultira(
+        &file,
+        &["play", "1", "Anna", "2", "Bob", "-1", "Carol", "-1"],
+    );
+    assert!(!String::from_utf8(without.stdout).unwrap().contains("Added"));
+    assert_eq!(ultira::read_data(&file).unwrap().history.len(), 2);
+
+    let with = ultira(
+        &file,
+        &[
+            "play",
+            "1",
+            "Anna",
+            "2",
+            "Bob",
+            "-1",
+            "Carol",
+            "-1",
+            "--auto-add",
+        ],
+    );
+    assert!(with.status.success());
+
+    let data = ultira::read_data(&file).unwrap();
+    assert_eq!(data.history.len(), 4);
+    assert!(
+        matches!(&data.history[2], ultira::Change::AddPlayer(addition) if addition.name == "Carol")
+    );
+    assert!(matches!(&data.history[3], ultira::Change::Play(_)));
+    assert!(data.evaluate().ratings["Carol"] < 0.0);
+
+    // Ambiguous names are still rejected
+    assert!(ultira(&file, &["add-player", "Annabel"]).status.success());
+    let ambiguous = ultira(
+        &file,
+        &[
+            "--prefer-prefix",
+            "play",
+            "1",
+            "Ann",
+            "2",
+            "Bob",
+            "-1",
+            "Dave",
+            "-1",
+            "--auto-add",
+        ],
+    );
+    assert!(!String::from_utf8(ambiguous.stdout)
+        .unwrap()
+        .contains("Dave"));
+    assert_eq!(ultira::read_data(&file).unwrap().history.len(), 5);
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn quiet_play() {
+    let file = temp_file("quiet-play");
+    assert!(ultira(&file, &["new", "-n"]).status.success());
+    for player in ["Anna", "Bob", "Carol"] {
+        assert!(ultira(&file, &["add-player", player]).status.success());
+    }
+
+    let loud = ultira(
+        &file,
+        &["play", "1", "Anna", "2", "Bob", "-1", "Carol", "-1"],
+    );
+    assert!(loud.status.success());
+    assert!(!loud.stdout.is_empty());
+
+    let quiet = ultira(
+        &file,
+        &["play", "1", "Anna", "-1", "Bob", "2", "Carol", "-1", "-q"],
+    );
+    assert!(quiet.status.success());
+    assert!(quiet.stdout.is_empty());
+
+    let data = ultira::read_data(&file).unwrap();
+    assert_eq!(data.history.len(), 5);
+    let ultira::Change::Play(play) = &data.history[4] else {
+        panic!("the quiet play wasn't appended");
+    };
+    assert_eq!(play.outcomes[1].player, "Bob");
+    assert_eq!(play.outcomes[1].score, 2);
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn check_json() {
+    let file = temp_file("check-json");
+    assert!(ultira(&file, &["new", "-n"]).status.success());
+    let mut data = ultira::read_data(&file).unwrap();
+    data.add_player("A".to_owned(), 0.0);
+    data.add_player("B".to_owned(), 0.0);
+    data.play(ultira::Play::now(
+        1,
+        [("A", 1), ("B", 1), ("C", 1)].map(|(player, score)| ultira::Outcome {
+            player: player.to_owned(),
+            score,
+        }),
+    ));
+    ultira::write_data(&file, &data).unwrap();
+
+    let check = ultira(&file, &["check", "--format", "json"]);
+    assert!(!check.status.success());
+
+    let problems: serde_json::Value = serde_json::from_slice(&check.stdout).unwrap();
+    let problems = problems.as_array().unwrap();
+    assert_eq!(problems.len(), 2);
+    assert_eq!(problems[0]["kind"], "unknown_player");
+    assert_eq!(problems[0]["history_index"], 2);
+    assert_eq!(problems[1]["kind"], "non_zero_sum");
+    assert!(problems[1]["message"].is_string());
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn yes() {
+    let file = temp_file("yes");
+    assert!(ultira(&file, &["new", "-n"]).status.success());
+    assert!(ultira(&file, &["add-player", "A"]).status.success());
+
+    // Without --yes, the closed stdin doesn't confirm
+    let new = ultira(&file, &["new"]);
+    assert!(String::from_utf8(new.stdout).unwrap().contains("aborting"));
+    assert_eq!(ultira::read_data(&file).unwrap().history.len(), 1);
+
+    let new = ultira(&file, &["--yes", "new"]);
+    assert!(new.status.success());
+    assert!(!String::from_utf8(new.stdout).unwrap().contains("aborting"));
+    assert!(ultira::read_data(&file).unwrap().history.is_empty());
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn internal_ratings() {
+    let file = temp_file("internal-ratings");
+    assert!(
+        ultira(&file, &["new", "-n", "--spread", "40", "--base", "1000"])
+            .status
+            .success()
+    );
+    for player in ["Anna", "Bob", "Carol"] {
+        assert!(ultira(&file, &["add-player", player]).status.success());
+    }
+    assert!(ultira(
+        &file,
+        &["play", "3", "Anna", "6", "Bob", "-2", "Carol", "-4", "-q"],
+    )
+    .status
+    .success());
+
+    let parse = |output: std::process::Output| -> Vec<(String, f64)> {
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let (rating, player) = line.trim().split_once(' ').unwrap();
+                (player.to_owned(), rating.parse().unwrap())
+            })
+            .collect()
+    };
+    let display = parse(ultira(&file, &["ratings", "--precision", "9"]));
+    let internal = parse(ultira(&file, &["ratings", "--internal"]));
+
+    assert_eq!(display.len(), 3);
+    assert_eq!(
+        display.iter().map(|(player, _)| player).collect::<Vec<_>>(),
+        internal
+            .iter()
+            .map(|(player, _)| player)
+            .collect::<Vec<_>>()
+    );
+    for ((_, display), (_, internal)) in display.iter().zip(&internal) {
+        assert!(((display - 1000.0) / 40.0 - internal).abs() < 1e-6);
+    }
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn export_timelines() {
+    let file = temp_file("export-timelines");
+    assert!(ultira(&file, &["new", "-n"]).status.success());
+    for player in ["Anna", "Bob", "Carol"] {
+        assert!(ultira(&file, &["add-player", player]).status.success());
+    }
+    for date in ["2024-01-01", "2024-01-02"] {
+        assert!(ultira(
+            &file,
+            &["play", "1", "Anna", "2", "Bob", "-1", "Carol", "-1", "-q", "-d", date],
+        )
+        .status
+        .success());
+    }
+    assert!(ultira(&file, &["add-player", "Dé/nes"]).status.success());
+    assert!(ultira(
+        &file,
+        &[
+            "play",
+            "1",
+            "Anna",
+            "2",
+            "Bob",
+            "-1",
+            "Dé/nes",
+            "-1",
+            "-q",
+            "-d",
+            "2024-01-03"
+        ],
+    )
+    .status
+    .success());
+
+    let dir = file.parent().unwrap().join("timelines");
+    let export = ultira(&file, &["export-timelines", dir.to_str().unwrap()]);
+    assert!(export.status.success());
+    assert!(String::from_utf8(export.stdout)
+        .unwrap()
+        .starts_with("Wrote 4 files"));
+
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+    for (name, rows) in [("Anna", 3), ("Bob", 3), ("Carol", 3), ("Dé_nes", 1)] {
+        let table = fs::read_to_string(dir.join(format!("{name}.tsv"))).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "datum\trating");
+        assert_eq!(lines.len(), 1 + rows);
+        assert!(lines[1..].iter().all(|line| line.split('\t').count() == 2));
+    }
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn audit_log() {
+    let file = temp_file("audit-log");
+    let log = file.with_file_name("audit.log");
+    assert!(ultira(&file, &["new", "-n"]).status.success());
+    let mut data = ultira::read_data(&file).unwrap();
+    data.config.audit_log = Some(log.clone());
+    ultira::write_data(&file, &data).unwrap();
+    for player in ["Anna", "Bob", "Carol"] {
+        assert!(ultira(&file, &["add-player", player]).status.success());
+    }
+    let lines = || fs::read_to_string(&log).unwrap().lines().count();
+    assert_eq!(lines(), 3);
+
+    let play = ultira(
+        &file,
+        &["play", "1", "Anna", "2", "Bob", "-1", "Carol", "-1", "-q"],
+    );
+    assert!(play.status.success());
+    assert_eq!(lines(), 4);
+    assert!(fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .last()
+        .unwrap()
+        .contains("Play(Play { game_count: 1.0, player_1: \"Anna\""));
+
+    // Aborted commands are logged too, read-only ones aren't
+    let aborted = ultira(
+        &file,
+        &["play", "1", "Anna", "2", "Bob", "-1", "Carol", "-2", "-q"],
+    );
+    assert_eq!(ultira::read_data(&file).unwrap().history.len(), 4);
+    assert!(String::from_utf8(aborted.stderr)
+        .unwrap()
+        .contains("don't sum to 0"));
+    assert_eq!(lines(), 5);
+    assert!(ultira(&file, &["ratings"]).status.success());
+    assert_eq!(lines(), 5);
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn import_history() {
+    let file = temp_file("import-history");
+    let import = file.with_file_name("history.json");
+    assert!(ultira(&file, &["new", "-n"]).status.success());
+    for player in ["Anna", "Bob", "Carol"] {
+        assert!(ultira(&file, &["add-player", player]).status.success());
+    }
+    let before = fs::read(&file).unwrap();
+
+    let play = |game_count, scores: [i64; 3]| {
+        ultira::Change::Play(ultira::Play {
+            game_count,
+            outcomes: [
+                ("Carol", scores[0]),
+                ("Bob", scores[1]),
+                ("Anna", scores[2]),
+            ]
+            .map(|(player, score)| ultira::Outcome {
+                player: player.to_owned(),
+                score,
+            }),
+            ..Default::default()
+        })
+    };
+    let import_history = |changes: &[ultira::Change]| {
+        fs::write(&import, ultira::to_json(&changes).unwrap()).unwrap();
+        ultira(
+            &file,
+            &["import-history", "--append", import.to_str().unwrap()],
+        )
+    };
+
+    for invalid in [play(0, [2, -1, -1]), play(1, [2, -1, 0])] {
+        let imported = import_history(&[play(1, [2, -1, -1]), invalid]);
+        assert!(!imported.status.success());
+        assert!(String::from_utf8(imported.stderr)
+            .unwrap()
+            .contains("Imported change 1 is invalid"));
+        assert_eq!(fs::read(&file).unwrap(), before);
+    }
+
+    assert!(import_history(&[play(1, [2, -1, -1])]).status.success());
+    let data = ultira::read_data(&file).unwrap();
+    let ultira::Change::Play(imported) = data.history.last().unwrap() else {
+        panic!("the play wasn't imported");
+    };
+    assert_eq!(imported.outcomes[0].player, "Anna");
+    assert!(ultira(&file, &["ratings"]).status.success());
+
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}